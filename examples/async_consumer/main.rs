@@ -0,0 +1,68 @@
+use async_trait::async_trait;
+use eyre::Result;
+use kcl::asynchronous::checkpointer::{AsyncCheckpoint, AsyncCheckpointShardEnd};
+use kcl::asynchronous::{run, AsyncProcessor};
+use kcl::checkpointer::{CheckpointTarget, ShardEndCheckpoint};
+use kcl::{InitializationInput, ProcessRecordsInput, ShardContext};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct MyPayload {
+    event_field: String,
+}
+
+struct MyConsumer;
+
+#[async_trait]
+impl AsyncProcessor for MyConsumer {
+    async fn initialize(
+        &mut self,
+        _context: &ShardContext,
+        _input: &InitializationInput,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn process_records(
+        &mut self,
+        _context: &ShardContext,
+        input: &ProcessRecordsInput,
+        checkpointer: &mut dyn AsyncCheckpoint,
+    ) -> Result<()> {
+        for record in &input.records {
+            match record.json::<MyPayload>() {
+                Ok(data) => println!("{:?}", data.event_field),
+                Err(e) => println!("{:?}", e),
+            }
+        }
+        checkpointer
+            .checkpoint(CheckpointTarget::LatestDelivered)
+            .await?;
+        Ok(())
+    }
+    async fn lease_lost(&mut self, _context: &ShardContext) -> Result<()> {
+        Ok(())
+    }
+    async fn shard_ended(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn AsyncCheckpointShardEnd,
+    ) -> Result<ShardEndCheckpoint> {
+        Ok(checkpointer.checkpoint().await?)
+    }
+    async fn shutdown_requested(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn AsyncCheckpoint,
+    ) -> Result<()> {
+        checkpointer
+            .checkpoint(CheckpointTarget::LatestDelivered)
+            .await?;
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    run(&mut MyConsumer {}).await;
+}
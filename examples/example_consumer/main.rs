@@ -1,7 +1,6 @@
-use kcl::checkpointer::Checkpointer;
-use kcl::reader::StdinReader;
-use kcl::writer::StdoutWriter;
-use kcl::{run, Processor, Record};
+use eyre::Result;
+use kcl::checkpointer::{Checkpoint, CheckpointShardEnd, CheckpointTarget, ShardEndCheckpoint};
+use kcl::{run, InitializationInput, ProcessRecordsInput, Processor, ShardContext};
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
@@ -11,34 +10,51 @@ struct MyPayload {
 
 struct MyConsumer;
 
-impl Processor<StdoutWriter, StdinReader> for MyConsumer {
-    fn initialize(&mut self, _shard_id: &str) {}
+impl Processor for MyConsumer {
+    fn initialize(&mut self, _context: &ShardContext, input: &InitializationInput) -> Result<()> {
+        println!(
+            "Processing {} from {:?}",
+            input.shard_id, input.sequence_number
+        );
+        Ok(())
+    }
 
     fn process_records(
         &mut self,
-        data: &[Record],
-        checkpointer: &mut Checkpointer<StdoutWriter, StdinReader>,
-    ) {
-        for record in data {
+        context: &ShardContext,
+        input: &ProcessRecordsInput,
+        checkpointer: &mut dyn Checkpoint,
+    ) -> Result<()> {
+        println!(
+            "Batch {}, {:?}ms behind latest",
+            context.batch_count, input.millis_behind_latest
+        );
+        for record in &input.records {
             match record.json::<MyPayload>() {
                 Ok(data) => println!("{:?}", data.event_field),
                 Err(e) => println!("{:?}", e),
             }
         }
-        checkpointer
-            .checkpoint(None, None)
-            .expect("Checkpoint to succeed.");
+        checkpointer.checkpoint(CheckpointTarget::LatestDelivered)?;
+        Ok(())
+    }
+    fn lease_lost(&mut self, _context: &ShardContext) -> Result<()> {
+        Ok(())
     }
-    fn lease_lost(&mut self) {}
-    fn shard_ended(&mut self, checkpointer: &mut Checkpointer<StdoutWriter, StdinReader>) {
-        checkpointer
-            .checkpoint(None, None)
-            .expect("Checkpoint to succeed.");
+    fn shard_ended(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn CheckpointShardEnd,
+    ) -> Result<ShardEndCheckpoint> {
+        Ok(checkpointer.checkpoint()?)
     }
-    fn shutdown_requested(&mut self, checkpointer: &mut Checkpointer<StdoutWriter, StdinReader>) {
-        checkpointer
-            .checkpoint(None, None)
-            .expect("Checkpoint to succeed.");
+    fn shutdown_requested(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn Checkpoint,
+    ) -> Result<()> {
+        checkpointer.checkpoint(CheckpointTarget::LatestDelivered)?;
+        Ok(())
     }
 }
 
@@ -0,0 +1,184 @@
+//! Procedural macros for [kcl](https://docs.rs/kcl), enabled by its `macros` feature and used
+//! through its re-exports, such as `#[kcl::processor]`.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Ident, ImplItem, ItemImpl, Token, Visibility};
+
+/// The callbacks of `kcl::Processor` that have a default when left out of the impl block.
+const HANDLERS: [&str; 7] = [
+    "initialize",
+    "process_records",
+    "lease_lost",
+    "shard_ended",
+    "shutdown_requested",
+    "daemon_gone",
+    "unknown_action",
+];
+
+/// Implements `kcl::Processor` from an impl block holding only the callbacks a processor cares
+/// about, see the `kcl` crate documentation.
+///
+/// Callbacks left out acknowledge their message without doing anything, except for `shard_ended`
+/// and `shutdown_requested`, which checkpoint at the end of the shard and at the latest delivered
+/// record respectively. Other items of the impl block are kept in an inherent impl, and attributes
+/// of the impl block apply to both impls. With `#[kcl::processor(main)]`, a `main` running a
+/// default instance of the processor with `kcl::run` is generated as well.
+#[proc_macro_attribute]
+pub fn processor(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = match Punctuated::<Ident, Token![,]>::parse_terminated.parse(args) {
+        Ok(args) => args,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    let item = parse_macro_input!(item as ItemImpl);
+
+    expand(args, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(args: Punctuated<Ident, Token![,]>, item: ItemImpl) -> syn::Result<TokenStream2> {
+    let mut main = false;
+    for arg in args {
+        match arg.to_string().as_str() {
+            "main" => main = true,
+            _ => return Err(syn::Error::new(arg.span(), "expected `main`")),
+        }
+    }
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(syn::Error::new(
+            path.span(),
+            "#[kcl::processor] expects an inherent impl block, without a trait",
+        ));
+    }
+
+    let ItemImpl {
+        attrs,
+        generics,
+        self_ty,
+        items,
+        ..
+    } = item;
+    let (mut handlers, others): (Vec<_>, Vec<_>) = items.into_iter().partition(|item| {
+        matches!(item, ImplItem::Fn(function) if HANDLERS.contains(&function.sig.ident.to_string().as_str()))
+    });
+    // Trait items cannot have a visibility of their own
+    for handler in &mut handlers {
+        if let ImplItem::Fn(function) = handler {
+            function.vis = Visibility::Inherited;
+        }
+    }
+    let defined = |name: &str| {
+        handlers
+            .iter()
+            .any(|item| matches!(item, ImplItem::Fn(function) if function.sig.ident == name))
+    };
+    let defaults = default_handlers()
+        .filter(|(name, _)| !defined(name))
+        .map(|(_, handler)| handler);
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    let mut expanded = quote! {
+        #(#attrs)*
+        impl #impl_generics ::kcl::Processor for #self_ty #where_clause {
+            #(#handlers)*
+            #(#defaults)*
+        }
+    };
+    if !others.is_empty() {
+        expanded.extend(quote! {
+            #(#attrs)*
+            impl #impl_generics #self_ty #where_clause {
+                #(#others)*
+            }
+        });
+    }
+    if main {
+        if !generics.params.is_empty() {
+            return Err(syn::Error::new(
+                generics.span(),
+                "#[kcl::processor(main)] cannot run a generic processor",
+            ));
+        }
+        expanded.extend(quote_spanned! {self_ty.span()=>
+            fn main() {
+                ::kcl::run(&mut <#self_ty as ::std::default::Default>::default());
+            }
+        });
+    }
+
+    Ok(expanded)
+}
+
+/// The callbacks a processor gets when leaving them out, by name.
+fn default_handlers() -> impl Iterator<Item = (&'static str, TokenStream2)> {
+    let result = quote!(::std::result::Result<(), ::kcl::__private::Report>);
+    let shard_end_result = quote! {
+        ::std::result::Result<::kcl::checkpointer::ShardEndCheckpoint, ::kcl::__private::Report>
+    };
+
+    [
+        (
+            "initialize",
+            quote! {
+                fn initialize(
+                    &mut self,
+                    _context: &::kcl::ShardContext,
+                    _input: &::kcl::InitializationInput,
+                ) -> #result {
+                    ::std::result::Result::Ok(())
+                }
+            },
+        ),
+        (
+            "process_records",
+            quote! {
+                fn process_records(
+                    &mut self,
+                    _context: &::kcl::ShardContext,
+                    _input: &::kcl::ProcessRecordsInput,
+                    _checkpointer: &mut dyn ::kcl::checkpointer::Checkpoint,
+                ) -> #result {
+                    ::std::result::Result::Ok(())
+                }
+            },
+        ),
+        (
+            "lease_lost",
+            quote! {
+                fn lease_lost(&mut self, _context: &::kcl::ShardContext) -> #result {
+                    ::std::result::Result::Ok(())
+                }
+            },
+        ),
+        (
+            "shard_ended",
+            quote! {
+                fn shard_ended(
+                    &mut self,
+                    _context: &::kcl::ShardContext,
+                    checkpointer: &mut dyn ::kcl::checkpointer::CheckpointShardEnd,
+                ) -> #shard_end_result {
+                    ::std::result::Result::Ok(checkpointer.checkpoint()?)
+                }
+            },
+        ),
+        (
+            "shutdown_requested",
+            quote! {
+                fn shutdown_requested(
+                    &mut self,
+                    _context: &::kcl::ShardContext,
+                    checkpointer: &mut dyn ::kcl::checkpointer::Checkpoint,
+                ) -> #result {
+                    checkpointer.checkpoint(::kcl::checkpointer::CheckpointTarget::LatestDelivered)?;
+                    ::std::result::Result::Ok(())
+                }
+            },
+        ),
+    ]
+    .into_iter()
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::messages::Record;
+use crate::sequence::ExtendedSequenceNumber;
+
+/// Tracks the completion of records processed out of order, e.g. by concurrent workers, so that the
+/// runner only checkpoints records for which every earlier record has been completed too.
+///
+/// Hand out an [`AckHandle`] for every delivered record with [`track`](Self::track), and configure
+/// the runner with a clone of the tracker, see
+/// [`RunnerBuilder::ack_tracker`](crate::RunnerBuilder::ack_tracker). Clones share their state.
+#[derive(Clone, Debug, Default)]
+pub struct AckTracker {
+    state: Arc<Mutex<AckState>>,
+}
+
+#[derive(Debug, Default)]
+struct AckState {
+    // Tracked records by position, with whether they have been acknowledged.
+    tracked: BTreeMap<ExtendedSequenceNumber, bool>,
+    watermark: Option<ExtendedSequenceNumber>,
+}
+
+impl AckTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking `record`, returning the handle to acknowledge it with once it is completed.
+    pub fn track(&self, record: &Record) -> AckHandle {
+        let position = record.sequence_number.clone();
+        self.state().tracked.insert(position.clone(), false);
+
+        AckHandle {
+            tracker: self.clone(),
+            position,
+        }
+    }
+
+    /// Starts tracking every record of a batch, see [`track`](Self::track).
+    pub fn track_all(&self, records: &[Record]) -> Vec<AckHandle> {
+        records.iter().map(|record| self.track(record)).collect()
+    }
+
+    /// The highest position up to which every tracked record has been acknowledged.
+    pub fn watermark(&self) -> Option<ExtendedSequenceNumber> {
+        self.state().watermark.clone()
+    }
+
+    /// The number of tracked records that have not been acknowledged yet.
+    pub fn outstanding(&self) -> usize {
+        self.state()
+            .tracked
+            .values()
+            .filter(|acked| !**acked)
+            .count()
+    }
+
+    fn ack(&self, position: ExtendedSequenceNumber) {
+        let mut state = self.state();
+        state.tracked.insert(position, true);
+        while let Some(entry) = state.tracked.first_entry() {
+            if !*entry.get() {
+                break;
+            }
+            let position = entry.remove_entry().0;
+            if state.watermark.as_ref() < Some(&position) {
+                state.watermark = Some(position);
+            }
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, AckState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Acknowledges the completion of a single record tracked by an [`AckTracker`].
+///
+/// A handle dropped without acknowledging keeps its record, and every record after it, from being
+/// checkpointed.
+#[derive(Debug)]
+pub struct AckHandle {
+    tracker: AckTracker,
+    position: ExtendedSequenceNumber,
+}
+
+impl AckHandle {
+    /// The position of the tracked record.
+    pub fn position(&self) -> &ExtendedSequenceNumber {
+        &self.position
+    }
+
+    /// Marks the record as completed.
+    pub fn ack(self) {
+        self.tracker.ack(self.position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(sequence_number: &str) -> Record {
+        Record {
+            raw_data: vec![],
+            partition_key: "1".to_string(),
+            explicit_hash_key: None,
+            sequence_number: sequence_number.parse().unwrap(),
+            approximate_arrival_timestamp: 0.0,
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn watermark_follows_contiguous_acks() {
+        let tracker = AckTracker::new();
+        let mut handles = tracker.track_all(&[record("1"), record("2"), record("3")]);
+        let third = handles.pop().unwrap();
+        let second = handles.pop().unwrap();
+        let first = handles.pop().unwrap();
+
+        third.ack();
+        assert_eq!(tracker.watermark(), None);
+        first.ack();
+        assert_eq!(tracker.watermark(), Some("1".parse().unwrap()));
+        second.ack();
+        assert_eq!(tracker.watermark(), Some("3".parse().unwrap()));
+        assert_eq!(tracker.outstanding(), 0);
+    }
+
+    #[test]
+    fn outstanding_ignores_acks_past_the_watermark() {
+        let tracker = AckTracker::new();
+        let mut handles = tracker.track_all(&[record("1"), record("2"), record("3")]);
+        let third = handles.pop().unwrap();
+        let second = handles.pop().unwrap();
+        let first = handles.pop().unwrap();
+
+        third.ack();
+        second.ack();
+        assert_eq!(tracker.outstanding(), 1);
+        first.ack();
+        assert_eq!(tracker.outstanding(), 0);
+    }
+
+    #[test]
+    fn watermark_spans_batches() {
+        let tracker = AckTracker::new();
+        let first_batch = tracker.track_all(&[record("1"), record("2")]);
+        let second_batch = tracker.track_all(&[record("3:0"), record("3:1")]);
+
+        for handle in second_batch {
+            handle.ack();
+        }
+        assert_eq!(tracker.watermark(), None);
+        for handle in first_batch {
+            handle.ack();
+        }
+        assert_eq!(tracker.watermark(), Some("3:1".parse().unwrap()));
+    }
+
+    #[test]
+    fn watermark_never_moves_back() {
+        let tracker = AckTracker::new();
+        tracker.track(&record("5")).ack();
+        tracker.track(&record("4")).ack();
+
+        assert_eq!(tracker.watermark(), Some("5".parse().unwrap()));
+    }
+}
@@ -0,0 +1,13 @@
+//! Async counterparts of the [`Processor`](crate::Processor) API, driven by a tokio based runner so
+//! the whole MultiLang loop can live on a single runtime.
+pub mod checkpointer;
+
+pub(crate) mod processor;
+pub mod reader;
+mod runner;
+pub(crate) mod typed;
+pub mod writer;
+
+pub use processor::AsyncProcessor;
+pub use runner::{run, tick};
+pub use typed::AsyncTypedProcessor;
@@ -0,0 +1,145 @@
+use crate::asynchronous::reader::AsyncInputReader;
+use crate::asynchronous::writer::AsyncOutputWriter;
+use async_trait::async_trait;
+use std::time::Instant;
+
+use crate::checkpointer::{
+    checkpoint_payload, parse_checkpoint_response, retry_delay, CheckpointRange, CheckpointTarget,
+    ShardEndCheckpoint,
+};
+use crate::messages::CheckpointError;
+use crate::runner::RunnerConfig;
+use crate::sequence::ExtendedSequenceNumber;
+
+/// Async counterpart of [`Checkpoint`](crate::checkpointer::Checkpoint), implemented by the
+/// [`AsyncCheckpointer`] handed to an [`AsyncProcessor`](crate::asynchronous::AsyncProcessor).
+#[async_trait]
+pub trait AsyncCheckpoint: Send {
+    /// Checkpoints at the given target, returning the checkpoint echoed back, if any.
+    async fn checkpoint(
+        &mut self,
+        target: CheckpointTarget<'_>,
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError>;
+}
+
+/// Async counterpart of [`Checkpointer`](crate::checkpointer::Checkpointer).
+pub struct AsyncCheckpointer<'a> {
+    writer: &'a mut dyn AsyncOutputWriter,
+    reader: &'a mut dyn AsyncInputReader,
+    config: &'a RunnerConfig,
+    range: CheckpointRange,
+    checkpointed: Option<ExtendedSequenceNumber>,
+}
+
+impl<'a> AsyncCheckpointer<'a> {
+    pub(crate) fn new(
+        writer: &'a mut dyn AsyncOutputWriter,
+        reader: &'a mut dyn AsyncInputReader,
+        config: &'a RunnerConfig,
+        range: CheckpointRange,
+    ) -> Self {
+        Self {
+            writer,
+            reader,
+            config,
+            range,
+            checkpointed: None,
+        }
+    }
+
+    /// Async counterpart of [`Checkpointer::checkpoint`](crate::checkpointer::Checkpointer::checkpoint).
+    pub async fn checkpoint(
+        &mut self,
+        target: CheckpointTarget<'_>,
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError> {
+        let requested = target.position();
+        let position = self.range.resolve(requested.as_ref())?;
+        let payload = checkpoint_payload(requested.as_ref())?;
+        let started = Instant::now();
+        let mut attempt = 1;
+        let echoed = loop {
+            match self.try_checkpoint(&payload).await {
+                Err(error) => match retry_delay(self.config, attempt, started, &error) {
+                    Some(delay) => {
+                        tokio::time::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    None => return Err(error),
+                },
+                Ok(echoed) => break echoed,
+            }
+        };
+        if let Some(position) = position {
+            self.range.checkpointed(&position);
+            self.checkpointed = Some(position);
+        }
+
+        Ok(echoed)
+    }
+
+    async fn try_checkpoint(
+        &mut self,
+        payload: &[u8],
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError> {
+        self.writer.write(payload).await?;
+        let next = self.reader.next().await?;
+        parse_checkpoint_response(&next, self.config.parse_mode)
+    }
+
+    /// Returns the position of the last successful checkpoint, if any.
+    pub(crate) fn checkpointed(&self) -> Option<&ExtendedSequenceNumber> {
+        self.checkpointed.as_ref()
+    }
+}
+
+#[async_trait]
+impl AsyncCheckpoint for AsyncCheckpointer<'_> {
+    async fn checkpoint(
+        &mut self,
+        target: CheckpointTarget<'_>,
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError> {
+        AsyncCheckpointer::checkpoint(self, target).await
+    }
+}
+
+/// Async counterpart of [`CheckpointShardEnd`](crate::checkpointer::CheckpointShardEnd),
+/// implemented by the [`AsyncShardEndCheckpointer`].
+#[async_trait]
+pub trait AsyncCheckpointShardEnd: Send {
+    /// Checkpoints at the end of the shard, returning the checkpoint to be returned from
+    /// `shard_ended`.
+    async fn checkpoint(&mut self) -> Result<ShardEndCheckpoint, CheckpointError>;
+}
+
+/// Async counterpart of [`ShardEndCheckpointer`](crate::checkpointer::ShardEndCheckpointer).
+pub struct AsyncShardEndCheckpointer<'a> {
+    checkpointer: AsyncCheckpointer<'a>,
+}
+
+impl<'a> AsyncShardEndCheckpointer<'a> {
+    pub(crate) fn new(checkpointer: AsyncCheckpointer<'a>) -> Self {
+        Self { checkpointer }
+    }
+
+    /// Async counterpart of
+    /// [`ShardEndCheckpointer::checkpoint`](crate::checkpointer::ShardEndCheckpointer::checkpoint).
+    pub async fn checkpoint(&mut self) -> Result<ShardEndCheckpoint, CheckpointError> {
+        let echoed = self
+            .checkpointer
+            .checkpoint(CheckpointTarget::ShardEnd)
+            .await?;
+
+        Ok(ShardEndCheckpoint::new(echoed))
+    }
+
+    pub(crate) fn into_inner(self) -> AsyncCheckpointer<'a> {
+        self.checkpointer
+    }
+}
+
+#[async_trait]
+impl AsyncCheckpointShardEnd for AsyncShardEndCheckpointer<'_> {
+    async fn checkpoint(&mut self) -> Result<ShardEndCheckpoint, CheckpointError> {
+        AsyncShardEndCheckpointer::checkpoint(self).await
+    }
+}
@@ -0,0 +1,50 @@
+use async_trait::async_trait;
+use eyre::Result;
+use serde_json::{Map, Value};
+
+use crate::asynchronous::checkpointer::{AsyncCheckpoint, AsyncCheckpointShardEnd};
+use crate::checkpointer::ShardEndCheckpoint;
+use crate::context::ShardContext;
+use crate::messages::{InitializationInput, ProcessRecordsInput};
+
+/// Async counterpart of [`Processor`](crate::Processor).
+#[async_trait]
+pub trait AsyncProcessor: Send {
+    async fn initialize(
+        &mut self,
+        context: &ShardContext,
+        input: &InitializationInput,
+    ) -> Result<()>;
+    async fn process_records(
+        &mut self,
+        context: &ShardContext,
+        input: &ProcessRecordsInput,
+        checkpoint: &mut dyn AsyncCheckpoint,
+    ) -> Result<()>;
+    async fn lease_lost(&mut self, context: &ShardContext) -> Result<()>;
+    async fn shard_ended(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn AsyncCheckpointShardEnd,
+    ) -> Result<ShardEndCheckpoint>;
+    async fn shutdown_requested(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn AsyncCheckpoint,
+    ) -> Result<()>;
+
+    /// Async counterpart of [`Processor::daemon_gone`](crate::Processor::daemon_gone).
+    async fn daemon_gone(&mut self, _context: &ShardContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Async counterpart of [`Processor::unknown_action`](crate::Processor::unknown_action).
+    async fn unknown_action(
+        &mut self,
+        _context: &ShardContext,
+        _action: &str,
+        _payload: &Map<String, Value>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use eyre::Result;
+use tokio::io::{self, AsyncBufReadExt, BufReader};
+
+#[async_trait]
+pub trait AsyncInputReader: Send {
+    /// Reads the next line sent by the daemon, returning an empty string once the input is closed.
+    async fn next(&mut self) -> Result<String>;
+}
+
+pub struct AsyncStdinReader {
+    stdin: BufReader<io::Stdin>,
+}
+
+impl Default for AsyncStdinReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsyncStdinReader {
+    pub fn new() -> Self {
+        Self {
+            stdin: BufReader::new(io::stdin()),
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncInputReader for AsyncStdinReader {
+    async fn next(&mut self) -> Result<String> {
+        let mut input = String::new();
+        self.stdin.read_line(&mut input).await?;
+
+        Ok(input)
+    }
+}
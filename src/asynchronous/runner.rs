@@ -0,0 +1,244 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::asynchronous::checkpointer::{AsyncCheckpointer, AsyncShardEndCheckpointer};
+use crate::asynchronous::processor::AsyncProcessor;
+use crate::asynchronous::reader::{AsyncInputReader, AsyncStdinReader};
+use crate::asynchronous::writer::{write_status, AsyncOutputWriter, AsyncStdoutWriter};
+use crate::checkpointer::{CheckpointRange, CheckpointTarget};
+use crate::context::ShardContext;
+use crate::failure::{FailurePolicy, ProcessorFailure};
+use crate::messages::{Message, UnknownPayload};
+use crate::runner::{
+    exit_code, Delivery, RunError, Runner, RunnerBuilder, RunnerConfig, TickOutcome,
+};
+use crate::watchdog::ParentWatchdog;
+
+/// Async counterpart of [`run`](crate::run).
+pub async fn run(processor: &mut (impl AsyncProcessor + ?Sized)) {
+    let result = Runner::async_builder().build().run_async(processor).await;
+    std::process::exit(exit_code(&result))
+}
+
+/// Async counterpart of [`tick`](crate::tick).
+pub async fn tick<W: AsyncOutputWriter, R: AsyncInputReader>(
+    processor: &mut (impl AsyncProcessor + ?Sized),
+    input_reader: &mut R,
+    output_writer: &mut W,
+) -> Result<TickOutcome, RunError> {
+    tick_with(
+        &RunnerConfig {
+            enforce_lifecycle: false,
+            ..RunnerConfig::default()
+        },
+        &mut ShardContext::default(),
+        processor,
+        input_reader,
+        output_writer,
+    )
+    .await
+}
+
+impl Runner<AsyncStdinReader, AsyncStdoutWriter> {
+    /// Creates a builder for a runner driving an [`AsyncProcessor`], talking to the daemon over
+    /// stdin and stdout unless configured otherwise.
+    pub fn async_builder() -> RunnerBuilder<AsyncStdinReader, AsyncStdoutWriter> {
+        RunnerBuilder::new(AsyncStdinReader::new, AsyncStdoutWriter::new)
+    }
+}
+
+impl<R: AsyncInputReader, W: AsyncOutputWriter> Runner<R, W> {
+    /// Async counterpart of [`Runner::run`].
+    pub async fn run_async(
+        &mut self,
+        processor: &mut (impl AsyncProcessor + ?Sized),
+    ) -> Result<(), RunError> {
+        let _watchdog = self.config.parent_watchdog.map(ParentWatchdog::start);
+        while self.tick_async(processor).await? == TickOutcome::Processed {}
+
+        Ok(())
+    }
+
+    /// Async counterpart of [`Runner::tick`].
+    pub async fn tick_async(
+        &mut self,
+        processor: &mut (impl AsyncProcessor + ?Sized),
+    ) -> Result<TickOutcome, RunError> {
+        tick_with(
+            &self.config,
+            &mut self.context,
+            processor,
+            &mut self.reader,
+            &mut self.writer,
+        )
+        .await
+    }
+}
+
+async fn tick_with<W: AsyncOutputWriter, R: AsyncInputReader>(
+    config: &RunnerConfig,
+    context: &mut ShardContext,
+    processor: &mut (impl AsyncProcessor + ?Sized),
+    input_reader: &mut R,
+    output_writer: &mut W,
+) -> Result<TickOutcome, RunError> {
+    let next = input_reader.next().await.map_err(RunError::Io)?;
+    let Some(delivery) = Delivery::receive(config, context, &next)? else {
+        with_failure_policy(
+            &config.failure_policy,
+            "daemonGone",
+            &mut (&mut *processor, &*context),
+            |(processor, context)| Box::pin(processor.daemon_gone(context)),
+        )
+        .await?;
+        return Ok(TickOutcome::InputClosed);
+    };
+    let message = delivery.message();
+
+    with_failure_policy(
+        &config.failure_policy,
+        message.action(),
+        &mut (
+            config,
+            &mut *context,
+            &mut *processor,
+            message,
+            &mut *output_writer,
+            &mut *input_reader,
+        ),
+        |(config, context, processor, message, output_writer, input_reader)| {
+            Box::pin(process_message(
+                config,
+                context,
+                &mut **processor,
+                message,
+                &mut **output_writer,
+                &mut **input_reader,
+            ))
+        },
+    )
+    .await?;
+    while let Some(delay) = delivery.next_drain_poll(config, context) {
+        tokio::time::sleep(delay).await;
+    }
+    if let Some(target) = delivery.due_checkpoint(config, context)? {
+        with_failure_policy(
+            &config.failure_policy,
+            message.action(),
+            &mut (
+                config,
+                &mut *context,
+                message,
+                &target,
+                &mut *output_writer,
+                &mut *input_reader,
+            ),
+            |(config, context, message, target, output_writer, input_reader)| {
+                Box::pin(checkpoint_for_processor(
+                    config,
+                    context,
+                    message,
+                    (*target).clone(),
+                    &mut **output_writer,
+                    &mut **input_reader,
+                ))
+            },
+        )
+        .await?;
+    }
+
+    write_status(output_writer, delivery.into_status())
+        .await
+        .map_err(RunError::Io)?;
+
+    Ok(TickOutcome::Processed)
+}
+
+type Attempt<'a> = Pin<Box<dyn Future<Output = eyre::Result<()>> + Send + 'a>>;
+
+/// Calls `callback` with `state` until it succeeds or `policy` gives up on it, sleeping between
+/// attempts without blocking the executor.
+///
+/// The state the callback borrows is passed in rather than captured, so that every attempt can
+/// reborrow it for the future it returns.
+async fn with_failure_policy<S: Send>(
+    policy: &FailurePolicy,
+    action: &str,
+    state: &mut S,
+    mut callback: impl for<'a> FnMut(&'a mut S) -> Attempt<'a>,
+) -> Result<(), ProcessorFailure> {
+    let mut attempt = 1;
+    while let Err(error) = callback(state).await {
+        match policy.on_failure(action, attempt, error)? {
+            Some(delay) => {
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            None => break,
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn process_message<W: AsyncOutputWriter, R: AsyncInputReader>(
+    config: &RunnerConfig,
+    context: &mut ShardContext,
+    processor: &mut (impl AsyncProcessor + ?Sized),
+    message: &Message,
+    output_writer: &mut W,
+    input_reader: &mut R,
+) -> eyre::Result<()> {
+    let range = CheckpointRange::new(config, context, message);
+    let mut checkpointer = AsyncCheckpointer::new(output_writer, input_reader, config, range);
+    let ctx = &*context;
+    let result = match message {
+        Message::Initialize(input) => processor.initialize(ctx, input).await,
+        Message::ProcessRecords(input) => {
+            processor
+                .process_records(ctx, input, &mut checkpointer)
+                .await
+        }
+        Message::LeaseLost(_) => processor.lease_lost(ctx).await,
+        Message::ShardEnded(_) => {
+            let mut shard_end = AsyncShardEndCheckpointer::new(checkpointer);
+            // The returned checkpoint may come from any checkpointer, so whether this shard was
+            // checkpointed at its end is checked against ours instead, see `due_checkpoint`
+            let result = processor
+                .shard_ended(ctx, &mut shard_end)
+                .await
+                .map(|_checkpoint| ());
+            checkpointer = shard_end.into_inner();
+            result
+        }
+        Message::ShutdownRequested(_) => processor.shutdown_requested(ctx, &mut checkpointer).await,
+        Message::Unknown(UnknownPayload { action, payload }) => {
+            processor.unknown_action(ctx, action, payload).await
+        }
+        Message::Checkpoint(_) => unreachable!("checkpoint messages are rejected before dispatch"),
+    };
+    if let Some(position) = checkpointer.checkpointed() {
+        context.on_checkpoint(position);
+    }
+
+    result
+}
+
+/// Async counterpart of the sync runner's checkpoint on behalf of the processor.
+async fn checkpoint_for_processor<W: AsyncOutputWriter, R: AsyncInputReader>(
+    config: &RunnerConfig,
+    context: &mut ShardContext,
+    message: &Message,
+    target: CheckpointTarget<'_>,
+    output_writer: &mut W,
+    input_reader: &mut R,
+) -> eyre::Result<()> {
+    let range = CheckpointRange::new(config, context, message);
+    let mut checkpointer = AsyncCheckpointer::new(output_writer, input_reader, config, range);
+    checkpointer.checkpoint(target).await?;
+    if let Some(position) = checkpointer.checkpointed() {
+        context.on_checkpoint(position);
+    }
+
+    Ok(())
+}
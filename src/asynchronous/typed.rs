@@ -0,0 +1,122 @@
+use async_trait::async_trait;
+use eyre::Result;
+use serde_json::{Map, Value};
+
+use crate::asynchronous::checkpointer::{AsyncCheckpoint, AsyncCheckpointShardEnd};
+use crate::asynchronous::processor::AsyncProcessor;
+use crate::checkpointer::ShardEndCheckpoint;
+use crate::codec::Codec;
+use crate::context::ShardContext;
+use crate::messages::{InitializationInput, ProcessRecordsInput};
+use crate::typed::{Typed, TypedBatch};
+
+/// Async counterpart of [`TypedProcessor`](crate::TypedProcessor), run by wrapping it in a
+/// [`Typed`].
+#[async_trait]
+pub trait AsyncTypedProcessor<T: Send + 'static>: Send {
+    async fn initialize(
+        &mut self,
+        context: &ShardContext,
+        input: &InitializationInput,
+    ) -> Result<()>;
+    async fn process_records(
+        &mut self,
+        context: &ShardContext,
+        batch: &TypedBatch<T>,
+        checkpoint: &mut dyn AsyncCheckpoint,
+    ) -> Result<()>;
+    async fn lease_lost(&mut self, context: &ShardContext) -> Result<()>;
+    async fn shard_ended(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn AsyncCheckpointShardEnd,
+    ) -> Result<ShardEndCheckpoint>;
+    async fn shutdown_requested(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn AsyncCheckpoint,
+    ) -> Result<()>;
+
+    /// Async counterpart of [`Processor::daemon_gone`](crate::Processor::daemon_gone).
+    async fn daemon_gone(&mut self, _context: &ShardContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Async counterpart of [`Processor::unknown_action`](crate::Processor::unknown_action).
+    async fn unknown_action(
+        &mut self,
+        _context: &ShardContext,
+        _action: &str,
+        _payload: &Map<String, Value>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<P, T, C> AsyncProcessor for Typed<P, T, C>
+where
+    P: AsyncTypedProcessor<T>,
+    T: Send + 'static,
+    C: Codec<T> + Send,
+{
+    async fn initialize(
+        &mut self,
+        context: &ShardContext,
+        input: &InitializationInput,
+    ) -> Result<()> {
+        self.processor_mut().initialize(context, input).await
+    }
+
+    async fn process_records(
+        &mut self,
+        context: &ShardContext,
+        input: &ProcessRecordsInput,
+        checkpoint: &mut dyn AsyncCheckpoint,
+    ) -> Result<()> {
+        let (processor, batch) = self.decoded(input)?;
+        processor
+            .process_records(context, batch, checkpoint)
+            .await?;
+        self.handled();
+
+        Ok(())
+    }
+
+    async fn lease_lost(&mut self, context: &ShardContext) -> Result<()> {
+        self.processor_mut().lease_lost(context).await
+    }
+
+    async fn shard_ended(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn AsyncCheckpointShardEnd,
+    ) -> Result<ShardEndCheckpoint> {
+        self.processor_mut().shard_ended(context, checkpoint).await
+    }
+
+    async fn shutdown_requested(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn AsyncCheckpoint,
+    ) -> Result<()> {
+        self.processor_mut()
+            .shutdown_requested(context, checkpoint)
+            .await
+    }
+
+    async fn daemon_gone(&mut self, context: &ShardContext) -> Result<()> {
+        self.processor_mut().daemon_gone(context).await
+    }
+
+    async fn unknown_action(
+        &mut self,
+        context: &ShardContext,
+        action: &str,
+        payload: &Map<String, Value>,
+    ) -> Result<()> {
+        self.processor_mut()
+            .unknown_action(context, action, payload)
+            .await
+    }
+}
@@ -0,0 +1,54 @@
+use async_trait::async_trait;
+use eyre::Result;
+use tokio::io::{self, AsyncWrite, AsyncWriteExt};
+
+use crate::responses::StatusResponse;
+use crate::writer::{protocol_output, status_payload};
+
+pub(crate) async fn write_status(
+    writer: &mut impl AsyncOutputWriter,
+    message: StatusResponse,
+) -> Result<()> {
+    let payload = status_payload(message)?;
+    writer.write(payload.as_slice()).await?;
+
+    Ok(())
+}
+
+#[async_trait]
+pub trait AsyncOutputWriter: Send {
+    async fn write(&mut self, payload: &[u8]) -> Result<()>;
+}
+
+pub struct AsyncStdoutWriter {
+    out: Box<dyn AsyncWrite + Send + Unpin>,
+}
+
+impl Default for AsyncStdoutWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsyncStdoutWriter {
+    /// Takes exclusive ownership of stdout for the protocol, redirecting any other output to stderr.
+    pub fn new() -> Self {
+        let out: Box<dyn AsyncWrite + Send + Unpin> =
+            match protocol_output().and_then(|file| file.try_clone().ok()) {
+                Some(file) => Box::new(tokio::fs::File::from_std(file)),
+                None => Box::new(io::stdout()),
+            };
+
+        Self { out }
+    }
+}
+
+#[async_trait]
+impl AsyncOutputWriter for AsyncStdoutWriter {
+    async fn write(&mut self, payload: &[u8]) -> Result<()> {
+        self.out.write_all(payload).await?;
+        self.out.flush().await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+/// Exponential backoff between retries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backoff {
+    /// The delay before the first retry.
+    pub initial: Duration,
+    /// The upper bound for any single delay.
+    pub max: Duration,
+    /// The factor the delay grows by after every attempt.
+    pub multiplier: u32,
+}
+
+impl Backoff {
+    /// Returns the delay to wait after the given (1-based) failed attempt.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .checked_pow(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(10),
+            multiplier: 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_grows_exponentially() {
+        let backoff = Backoff::default();
+
+        assert_eq!(backoff.delay(1), Duration::from_millis(100));
+        assert_eq!(backoff.delay(2), Duration::from_millis(200));
+        assert_eq!(backoff.delay(4), Duration::from_millis(800));
+    }
+
+    #[test]
+    fn delay_is_capped() {
+        let backoff = Backoff::default();
+
+        assert_eq!(backoff.delay(8), Duration::from_secs(10));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(10));
+    }
+}
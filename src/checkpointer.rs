@@ -1,57 +1,329 @@
+use std::time::{Duration, Instant};
+
 use crate::writer::OutputWriter;
 
-use crate::messages::CheckpointError::UnexpectedResponse;
-use crate::messages::Message::Checkpoint;
-use crate::messages::{parse_message, CheckpointError, CheckpointWithErrorPayload};
+use crate::context::ShardContext;
+use crate::lifecycle::ShardState;
+use crate::messages::CheckpointError::{NotAllowed, OutOfRange, UnexpectedResponse};
+use crate::messages::{parse_message, CheckpointWithErrorPayload, Message, ParseMode, Record};
 use crate::reader::InputReader;
+use crate::runner::RunnerConfig;
+use crate::sequence::ExtendedSequenceNumber;
 use serde::Serialize;
 
-pub struct Checkpointer<'a, W: OutputWriter, R: InputReader> {
-    writer: &'a mut W,
-    reader: &'a mut R,
+pub use crate::messages::CheckpointError;
+
+/// Checkpoints a shard, handed to a [`Processor`](crate::Processor) by the runner as a
+/// [`Checkpointer`].
+///
+/// Processors only see the trait, so their callbacks can be unit tested without a daemon, e.g. with
+/// the `RecordingCheckpointer` of the `testing` feature.
+pub trait Checkpoint {
+    /// Checkpoints at the given target, returning the checkpoint echoed back, if any.
+    fn checkpoint(
+        &mut self,
+        target: CheckpointTarget<'_>,
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError>;
+}
+
+/// The [`Checkpoint`] implementation the runner hands to a [`Processor`](crate::Processor),
+/// checkpointing through the daemon the runner talks to.
+pub struct Checkpointer<'a> {
+    writer: &'a mut dyn OutputWriter,
+    reader: &'a mut dyn InputReader,
+    config: &'a RunnerConfig,
+    range: CheckpointRange,
+    checkpointed: Option<ExtendedSequenceNumber>,
 }
 
-impl<'a, W: OutputWriter, R: InputReader> Checkpointer<'a, W, R> {
-    pub(crate) fn new(writer: &'a mut W, reader: &'a mut R) -> Self {
-        Self { writer, reader }
+impl<'a> Checkpointer<'a> {
+    pub(crate) fn new(
+        writer: &'a mut dyn OutputWriter,
+        reader: &'a mut dyn InputReader,
+        config: &'a RunnerConfig,
+        range: CheckpointRange,
+    ) -> Self {
+        Self {
+            writer,
+            reader,
+            config,
+            range,
+            checkpointed: None,
+        }
     }
 
-    /// Checkpoints at a particular sequence number you provide or if no sequence number is given, the checkpoint will
-    /// be at the end of the most recently delivered list of records.
+    /// Checkpoints at the given target, see [`CheckpointTarget`].
+    ///
+    /// A position before the last checkpoint or after the last delivered record is rejected with
+    /// [`CheckpointError::OutOfRange`] without asking the daemon, where the runner knows those
+    /// bounds. Retryable errors are retried as configured with
+    /// [`RunnerBuilder::checkpoint_retry`](crate::RunnerBuilder::checkpoint_retry).
     ///
     /// # Arguments
     ///
-    /// * `sequence_number`: the sequence number to checkpoint
-    /// * `sub_sequence_number`: the sub sequence number generated by KPL
+    /// * `target`: where to checkpoint
     ///
-    /// returns: Result<(), Report>
+    /// returns: the checkpoint echoed back by the daemon, if it sent one
     ///
     pub fn checkpoint(
         &mut self,
-        sequence_number: Option<String>,
-        sub_sequence_number: Option<u64>,
-    ) -> Result<(), CheckpointError> {
-        let message = CheckpointMessage {
-            action: "checkpoint".to_string(),
-            sequence_number,
-            sub_sequence_number,
+        target: CheckpointTarget<'_>,
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError> {
+        let requested = target.position();
+        let position = self.range.resolve(requested.as_ref())?;
+        let payload = checkpoint_payload(requested.as_ref())?;
+        let started = Instant::now();
+        let mut attempt = 1;
+        let echoed = loop {
+            match self.try_checkpoint(&payload) {
+                Err(error) => match retry_delay(self.config, attempt, started, &error) {
+                    Some(delay) => {
+                        std::thread::sleep(delay);
+                        attempt += 1;
+                    }
+                    None => return Err(error),
+                },
+                Ok(echoed) => break echoed,
+            }
         };
-        let mut payload = serde_json::to_vec(&message)?;
-        payload.push(b'\n');
-        self.writer.write(payload.as_slice())?;
+        if let Some(position) = position {
+            self.range.checkpointed(&position);
+            self.checkpointed = Some(position);
+        }
+
+        Ok(echoed)
+    }
+
+    fn try_checkpoint(
+        &mut self,
+        payload: &[u8],
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError> {
+        self.writer.write(payload)?;
         let next = self.reader.next()?;
-        let message = parse_message(&next)?;
-        match message {
-            Checkpoint(CheckpointWithErrorPayload {
-                checkpoint: _,
-                error,
-            }) => match error {
-                None => Ok(()),
-                Some(error) => Err(error),
-            },
-            _ => Err(UnexpectedResponse),
+        parse_checkpoint_response(&next, self.config.parse_mode)
+    }
+
+    /// Returns the position of the last successful checkpoint, if any.
+    pub(crate) fn checkpointed(&self) -> Option<&ExtendedSequenceNumber> {
+        self.checkpointed.as_ref()
+    }
+}
+
+impl Checkpoint for Checkpointer<'_> {
+    fn checkpoint(
+        &mut self,
+        target: CheckpointTarget<'_>,
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError> {
+        Checkpointer::checkpoint(self, target)
+    }
+}
+
+/// Checkpoints a shard at its end, handed to
+/// [`Processor::shard_ended`](crate::Processor::shard_ended) by the runner as a
+/// [`ShardEndCheckpointer`].
+pub trait CheckpointShardEnd {
+    /// Checkpoints at the end of the shard, returning the checkpoint to be returned from
+    /// `shard_ended`.
+    fn checkpoint(&mut self) -> Result<ShardEndCheckpoint, CheckpointError>;
+}
+
+/// The checkpointer handed to [`Processor::shard_ended`](crate::Processor::shard_ended), which can
+/// only checkpoint at the end of the shard.
+///
+/// The daemon only moves on to the child shards once the ended shard has been checkpointed at its
+/// end, so `shard_ended` must return the [`ShardEndCheckpoint`] obtained from
+/// [`checkpoint`](Self::checkpoint).
+pub struct ShardEndCheckpointer<'a> {
+    checkpointer: Checkpointer<'a>,
+}
+
+impl<'a> ShardEndCheckpointer<'a> {
+    pub(crate) fn new(checkpointer: Checkpointer<'a>) -> Self {
+        Self { checkpointer }
+    }
+
+    /// Checkpoints at the end of the shard, see [`Checkpointer::checkpoint`].
+    ///
+    /// returns: the checkpoint, to be returned from `shard_ended`
+    ///
+    pub fn checkpoint(&mut self) -> Result<ShardEndCheckpoint, CheckpointError> {
+        let echoed = self.checkpointer.checkpoint(CheckpointTarget::ShardEnd)?;
+
+        Ok(ShardEndCheckpoint::new(echoed))
+    }
+
+    pub(crate) fn into_inner(self) -> Checkpointer<'a> {
+        self.checkpointer
+    }
+}
+
+impl CheckpointShardEnd for ShardEndCheckpointer<'_> {
+    fn checkpoint(&mut self) -> Result<ShardEndCheckpoint, CheckpointError> {
+        ShardEndCheckpointer::checkpoint(self)
+    }
+}
+
+/// A checkpoint of a shard at its end, returned by [`CheckpointShardEnd::checkpoint`] so that
+/// `shard_ended` cannot return without checkpointing.
+///
+/// Other implementations of [`CheckpointShardEnd`] hand it out too, so it does not prove that the
+/// ended shard was checkpointed: the runner checks that itself, and handles a shard that was not
+/// as configured with [`RunnerBuilder::shard_end_policy`](crate::RunnerBuilder::shard_end_policy).
+#[derive(Debug)]
+#[must_use = "shard_ended must return the shard end checkpoint"]
+pub struct ShardEndCheckpoint {
+    echoed: Option<ExtendedSequenceNumber>,
+}
+
+impl ShardEndCheckpoint {
+    pub(crate) fn new(echoed: Option<ExtendedSequenceNumber>) -> Self {
+        Self { echoed }
+    }
+
+    /// The checkpoint echoed back by the daemon, if it sent one.
+    pub fn echoed(&self) -> Option<&ExtendedSequenceNumber> {
+        self.echoed.as_ref()
+    }
+}
+
+/// Where a [`Checkpointer`] checkpoints.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CheckpointTarget<'a> {
+    /// After the last record of the most recently delivered batch.
+    LatestDelivered,
+    /// At a delivered record, so that processing resumes after it.
+    Record(&'a Record),
+    /// At a position within the delivered records.
+    At(ExtendedSequenceNumber),
+    /// At the end of a shard that has ended, only valid while handling `shardEnded`, see
+    /// [`CheckpointShardEnd`].
+    ShardEnd,
+}
+
+impl CheckpointTarget<'_> {
+    /// The requested position, where `None` leaves it to the daemon.
+    pub(crate) fn position(&self) -> Option<ExtendedSequenceNumber> {
+        match self {
+            CheckpointTarget::LatestDelivered => None,
+            CheckpointTarget::Record(record) => Some(record.sequence_number.clone()),
+            CheckpointTarget::At(position) => Some(position.clone()),
+            CheckpointTarget::ShardEnd => Some(ExtendedSequenceNumber::SHARD_END),
+        }
+    }
+}
+
+/// The positions a processor may checkpoint at while handling a message: from the last checkpoint
+/// up to the last record delivered, or the end of the shard once it has ended. Checkpointing is not
+/// possible at all in some lifecycle states.
+///
+/// Bounds the context does not know about are left open, e.g. with the stateless
+/// [`tick`](crate::tick), leaving it to the daemon to reject such checkpoints.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CheckpointRange {
+    lowest: Option<ExtendedSequenceNumber>,
+    highest: Option<ExtendedSequenceNumber>,
+    // The state to check checkpoints against, if the lifecycle is enforced.
+    state: Option<ShardState>,
+}
+
+impl CheckpointRange {
+    pub(crate) fn new(config: &RunnerConfig, context: &ShardContext, message: &Message) -> Self {
+        let highest = match message {
+            Message::ShardEnded(_) => Some(ExtendedSequenceNumber::SHARD_END),
+            _ => context.last_delivered.clone(),
+        };
+
+        Self {
+            lowest: context
+                .last_checkpointed
+                .clone()
+                .or_else(|| context.starting_position.clone()),
+            highest,
+            state: Some(context.state).filter(|_| config.enforce_lifecycle),
         }
     }
+
+    /// Returns the position a checkpoint at `sequence_number` would be recorded at, where no
+    /// sequence number means the highest position, or an error if it is out of range.
+    pub(crate) fn resolve(
+        &self,
+        sequence_number: Option<&ExtendedSequenceNumber>,
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError> {
+        if let Some(state) = self.state.filter(|state| !state.allows_checkpoint()) {
+            return Err(NotAllowed(state));
+        }
+        let Some(position) = sequence_number else {
+            return Ok(self.highest.clone());
+        };
+        let in_range = (!position.is_sentinel() || *position == ExtendedSequenceNumber::SHARD_END)
+            && self.lowest.as_ref().is_none_or(|lowest| lowest <= position)
+            && self
+                .highest
+                .as_ref()
+                .is_none_or(|highest| position <= highest);
+        if !in_range {
+            return Err(OutOfRange(position.clone()));
+        }
+
+        Ok(Some(position.clone()))
+    }
+
+    pub(crate) fn checkpointed(&mut self, position: &ExtendedSequenceNumber) {
+        self.lowest = Some(position.clone());
+    }
+}
+
+pub(crate) fn checkpoint_payload(
+    sequence_number: Option<&ExtendedSequenceNumber>,
+) -> Result<Vec<u8>, CheckpointError> {
+    // The daemon checkpoints an ended shard at its end when no sequence number is given.
+    let sequence_number =
+        sequence_number.filter(|position| **position != ExtendedSequenceNumber::SHARD_END);
+    let message = CheckpointMessage {
+        action: "checkpoint".to_string(),
+        sequence_number: sequence_number.map(|position| position.sequence_number().to_string()),
+        sub_sequence_number: sequence_number.map(ExtendedSequenceNumber::sub_sequence_number),
+    };
+    let mut payload = serde_json::to_vec(&message)?;
+    payload.push(b'\n');
+
+    Ok(payload)
+}
+
+/// Returns how long to wait before retrying a failed checkpoint, if it should be retried.
+pub(crate) fn retry_delay(
+    config: &RunnerConfig,
+    attempt: u32,
+    started: Instant,
+    error: &CheckpointError,
+) -> Option<Duration> {
+    config
+        .checkpoint_retry
+        .as_ref()
+        .and_then(|policy| policy.on_failure(attempt, started, error))
+}
+
+/// Returns the checkpoint echoed by the daemon, or the error it reported.
+pub(crate) fn parse_checkpoint_response(
+    response: &str,
+    parse_mode: ParseMode,
+) -> Result<Option<ExtendedSequenceNumber>, CheckpointError> {
+    let message = parse_message(response, parse_mode)?;
+    match message {
+        Message::Checkpoint(CheckpointWithErrorPayload {
+            error: Some(error), ..
+        }) => Err(error),
+        Message::Checkpoint(CheckpointWithErrorPayload {
+            checkpoint,
+            sub_sequence_number,
+            ..
+        }) => Ok(checkpoint
+            .map(|checkpoint| {
+                ExtendedSequenceNumber::new(&checkpoint, sub_sequence_number.unwrap_or_default())
+            })
+            .transpose()?),
+        _ => Err(UnexpectedResponse),
+    }
 }
 
 #[derive(Debug, Serialize)]
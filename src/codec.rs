@@ -0,0 +1,231 @@
+//! Decoding of record payloads, see [`TypedProcessor`](crate::TypedProcessor) and
+//! [`Record::decode`](crate::Record::decode).
+//!
+//! A [`Decoder`] chains decompression, by sniffing the magic bytes of the payload or as configured,
+//! with a [`Codec`] deserializing the result. Each [`Compression`] algorithm is behind a cargo
+//! feature of its own: `gzip`, `zstd` and `snappy`.
+use std::borrow::Cow;
+#[cfg(any(feature = "gzip", feature = "snappy"))]
+use std::io::Read;
+
+use serde::de::DeserializeOwned;
+
+/// Decodes the payload of a record into a `T`.
+pub trait Codec<T> {
+    fn decode(&self, data: &[u8]) -> eyre::Result<T>;
+}
+
+/// Decodes payloads as JSON, the default codec.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Json;
+
+impl<T: DeserializeOwned> Codec<T> for Json {
+    fn decode(&self, data: &[u8]) -> eyre::Result<T> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+impl<T, F: Fn(&[u8]) -> eyre::Result<T>> Codec<T> for F {
+    fn decode(&self, data: &[u8]) -> eyre::Result<T> {
+        self(data)
+    }
+}
+
+/// A compression algorithm payloads can be decompressed from, each enabled by the feature of the
+/// same name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// Either the framed format, recognised by its stream identifier, or the raw format.
+    #[cfg(feature = "snappy")]
+    Snappy,
+}
+
+#[cfg(feature = "snappy")]
+const SNAPPY_STREAM_IDENTIFIER: &[u8] = b"\xff\x06\x00\x00sNaPpY";
+
+impl Compression {
+    /// Every enabled algorithm.
+    pub const ENABLED: &'static [Compression] = &[
+        #[cfg(feature = "gzip")]
+        Compression::Gzip,
+        #[cfg(feature = "zstd")]
+        Compression::Zstd,
+        #[cfg(feature = "snappy")]
+        Compression::Snappy,
+    ];
+
+    /// The bytes every payload compressed with this algorithm starts with. Raw snappy has none, so
+    /// only its framed format is recognised.
+    pub fn magic_bytes(self) -> &'static [u8] {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => &[0x1f, 0x8b],
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => &[0x28, 0xb5, 0x2f, 0xfd],
+            #[cfg(feature = "snappy")]
+            Compression::Snappy => SNAPPY_STREAM_IDENTIFIER,
+        }
+    }
+
+    /// Returns the enabled algorithm `data` was compressed with, judging by its magic bytes.
+    pub fn sniff(data: &[u8]) -> Option<Compression> {
+        Self::ENABLED
+            .iter()
+            .copied()
+            .find(|compression| data.starts_with(compression.magic_bytes()))
+    }
+
+    #[cfg_attr(
+        not(any(feature = "gzip", feature = "zstd", feature = "snappy")),
+        allow(unused_variables)
+    )]
+    pub fn decompress(self, data: &[u8]) -> eyre::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                let mut decompressed = Vec::new();
+                flate2::read::MultiGzDecoder::new(data).read_to_end(&mut decompressed)?;
+                Ok(decompressed)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(zstd::stream::decode_all(data)?),
+            #[cfg(feature = "snappy")]
+            Compression::Snappy if data.starts_with(SNAPPY_STREAM_IDENTIFIER) => {
+                let mut decompressed = Vec::new();
+                snap::read::FrameDecoder::new(data).read_to_end(&mut decompressed)?;
+                Ok(decompressed)
+            }
+            #[cfg(feature = "snappy")]
+            Compression::Snappy => Ok(snap::raw::Decoder::new().decompress_vec(data)?),
+        }
+    }
+}
+
+/// How a [`Decoder`] decompresses payloads before decoding them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Decompression {
+    /// Decompresses payloads starting with the magic bytes of an enabled [`Compression`], and
+    /// passes the others through as they are.
+    #[default]
+    Sniff,
+    /// Decompresses every payload with the given algorithm, failing on those not compressed with it.
+    With(Compression),
+    /// Passes every payload through as it is.
+    Disabled,
+}
+
+/// A [`Codec`] decompressing payloads before handing them to another codec, JSON by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Decoder<C = Json> {
+    decompression: Decompression,
+    codec: C,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<C> Decoder<C> {
+    /// Decompresses payloads as configured by `decompression`, see [`Decompression`].
+    pub fn decompression(mut self, decompression: Decompression) -> Self {
+        self.decompression = decompression;
+        self
+    }
+
+    /// Decodes decompressed payloads with `codec` instead.
+    pub fn codec<D>(self, codec: D) -> Decoder<D> {
+        Decoder {
+            decompression: self.decompression,
+            codec,
+        }
+    }
+
+    /// Decompresses `data` as configured, without decoding it.
+    pub fn decompress<'a>(&self, data: &'a [u8]) -> eyre::Result<Cow<'a, [u8]>> {
+        let compression = match self.decompression {
+            Decompression::Sniff => Compression::sniff(data),
+            Decompression::With(compression) => Some(compression),
+            Decompression::Disabled => None,
+        };
+
+        match compression {
+            Some(compression) => Ok(Cow::Owned(compression.decompress(data)?)),
+            None => Ok(Cow::Borrowed(data)),
+        }
+    }
+}
+
+impl<T, C: Codec<T>> Codec<T> for Decoder<C> {
+    fn decode(&self, data: &[u8]) -> eyre::Result<T> {
+        let decompressed = self.decompress(data)?;
+        self.codec.decode(&decompressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_uncompressed_payloads_through() {
+        let decoder = Decoder::new();
+
+        assert!(matches!(
+            decoder.decompress(b"{\"id\":1}").unwrap(),
+            Cow::Borrowed(b"{\"id\":1}")
+        ));
+        let decoded: serde_json::Value = decoder.decode(b"{\"id\":1}").unwrap();
+        assert_eq!(decoded, serde_json::json!({"id": 1}));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn decompresses_gzip() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"{\"id\":1}").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(Compression::sniff(&compressed), Some(Compression::Gzip));
+        let decoded: serde_json::Value = Decoder::new().decode(&compressed).unwrap();
+        assert_eq!(decoded, serde_json::json!({"id": 1}));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn decompresses_zstd() {
+        let compressed = zstd::stream::encode_all(&b"{\"id\":1}"[..], 0).unwrap();
+
+        assert_eq!(Compression::sniff(&compressed), Some(Compression::Zstd));
+        let decoded: serde_json::Value = Decoder::new().decode(&compressed).unwrap();
+        assert_eq!(decoded, serde_json::json!({"id": 1}));
+    }
+
+    #[cfg(feature = "snappy")]
+    #[test]
+    fn decompresses_snappy() {
+        use std::io::Write;
+
+        let mut encoder = snap::write::FrameEncoder::new(Vec::new());
+        encoder.write_all(b"{\"id\":1}").unwrap();
+        let framed = encoder.into_inner().unwrap();
+        let raw = snap::raw::Encoder::new()
+            .compress_vec(b"{\"id\":1}")
+            .unwrap();
+
+        assert_eq!(Compression::sniff(&framed), Some(Compression::Snappy));
+        assert_eq!(Compression::sniff(&raw), None);
+        let decoder = Decoder::new().decompression(Decompression::With(Compression::Snappy));
+        for compressed in [framed, raw] {
+            let decoded: serde_json::Value = decoder.decode(&compressed).unwrap();
+            assert_eq!(decoded, serde_json::json!({"id": 1}));
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::{Instant, SystemTime};
+
+use crate::lifecycle::{LifecycleViolation, ShardState};
+use crate::messages::Message;
+use crate::sequence::ExtendedSequenceNumber;
+
+/// Bookkeeping about the shard being processed, maintained by the runner and passed to every
+/// [`Processor`](crate::Processor) callback.
+///
+/// The context is updated with each message before the processor sees it, and with any successful
+/// checkpoint once the callback making it returns.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShardContext {
+    /// Where the shard is in its lifecycle, as of the message being handled.
+    pub state: ShardState,
+    /// The shard being processed, known once the processor has been initialized.
+    pub shard_id: Option<String>,
+    /// The position processing resumed after, as sent with `initialize`.
+    pub starting_position: Option<ExtendedSequenceNumber>,
+    /// The number of batches of records delivered so far, including the one being processed.
+    pub batch_count: u64,
+    /// The position of the last record delivered.
+    pub last_delivered: Option<ExtendedSequenceNumber>,
+    /// The position of the last successful checkpoint.
+    pub last_checkpointed: Option<ExtendedSequenceNumber>,
+    /// When the last message from the daemon was received.
+    pub last_message_at: Option<SystemTime>,
+    /// When the last batch of records was received.
+    pub last_batch_at: Option<SystemTime>,
+    /// The records delivered since the last checkpoint covering all of them.
+    pub since_checkpoint: CheckpointProgress,
+    /// When the processor must be done draining, once shutdown has been requested with a
+    /// [`ShutdownDrain`](crate::ShutdownDrain) configured.
+    pub drain_deadline: Option<Instant>,
+}
+
+/// Records delivered since the last checkpoint, as used by a
+/// [`CheckpointStrategy`](crate::CheckpointStrategy).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheckpointProgress {
+    pub batches: u64,
+    pub records: u64,
+    /// The total size of the records' data.
+    pub bytes: u64,
+    /// When the first of the batches arrived.
+    pub since: Option<SystemTime>,
+}
+
+impl ShardContext {
+    /// Records `message`, returning the violation if the lifecycle does not allow it, in which case
+    /// the context is left unchanged.
+    pub(crate) fn on_message(&mut self, message: &Message) -> Result<(), LifecycleViolation> {
+        self.state = self.state.on_message(message)?;
+        self.record(message);
+
+        Ok(())
+    }
+
+    /// Records `message` without checking it against the lifecycle, leaving the state unchanged.
+    pub(crate) fn record(&mut self, message: &Message) {
+        let now = SystemTime::now();
+        self.last_message_at = Some(now);
+        match message {
+            Message::Initialize(input) => {
+                self.shard_id = Some(input.shard_id.clone());
+                self.starting_position = input.sequence_number.clone();
+            }
+            Message::ProcessRecords(input) => {
+                self.batch_count += 1;
+                self.last_batch_at = Some(now);
+                let progress = &mut self.since_checkpoint;
+                progress.batches += 1;
+                progress.records += input.records.len() as u64;
+                progress.bytes += input
+                    .records
+                    .iter()
+                    .map(|record| record.raw_data.len() as u64)
+                    .sum::<u64>();
+                progress.since.get_or_insert(now);
+                if let Some(record) = input.records.iter().map(|r| &r.sequence_number).max() {
+                    self.last_delivered = Some(record.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn on_checkpoint(&mut self, position: &ExtendedSequenceNumber) {
+        self.last_checkpointed = Some(position.clone());
+        if self
+            .last_delivered
+            .as_ref()
+            .is_none_or(|delivered| delivered <= position)
+        {
+            self.since_checkpoint = CheckpointProgress::default();
+        }
+    }
+}
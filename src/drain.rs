@@ -0,0 +1,138 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::ack::AckTracker;
+use crate::context::ShardContext;
+
+/// Called when a shutdown drain misses its deadline, see [`ShutdownDrain::on_missed`].
+pub type DrainHook = Arc<dyn Fn(&MissedDrain) + Send + Sync>;
+
+/// How the runner drains a shard when the daemon requests a shutdown, see
+/// [`RunnerBuilder::shutdown_drain`](crate::RunnerBuilder::shutdown_drain).
+///
+/// The daemon only waits a bounded time for a processor to shut down. With a drain configured, the
+/// processor finds the deadline to flush its buffered records by in
+/// [`ShardContext::drain_deadline`]. Once
+/// [`Processor::shutdown_requested`](crate::Processor::shutdown_requested) returns, the runner
+/// waits until the deadline for any records still outstanding in its
+/// [`AckTracker`](crate::AckTracker), then checkpoints the highest completed position.
+#[derive(Clone)]
+pub struct ShutdownDrain {
+    /// How long the processor has to drain, which should leave the daemon time to checkpoint.
+    pub deadline: Duration,
+    /// How often outstanding records are checked for while waiting.
+    pub poll_interval: Duration,
+    /// Called when the deadline was missed, e.g. to alert on records that will be redelivered.
+    pub on_missed: Option<DrainHook>,
+}
+
+/// A shutdown drain that missed its deadline, as reported to [`ShutdownDrain::on_missed`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissedDrain {
+    /// The number of records still outstanding, which will be processed again after a restart.
+    pub outstanding: usize,
+    /// How long past the deadline the drain took.
+    pub overrun: Duration,
+}
+
+impl ShutdownDrain {
+    pub fn new(deadline: Duration) -> Self {
+        Self {
+            deadline,
+            poll_interval: Duration::from_millis(10),
+            on_missed: None,
+        }
+    }
+
+    /// Returns how long to wait before checking for outstanding records again, or `None` once
+    /// there are none or the deadline has passed.
+    pub(crate) fn next_poll(
+        &self,
+        context: &ShardContext,
+        acks: Option<&AckTracker>,
+    ) -> Option<Duration> {
+        let remaining = context
+            .drain_deadline?
+            .checked_duration_since(Instant::now())?;
+        let outstanding = acks.map_or(0, AckTracker::outstanding);
+
+        (outstanding > 0).then(|| self.poll_interval.min(remaining))
+    }
+
+    /// Reports the drain if it missed its deadline.
+    pub(crate) fn finish(&self, context: &ShardContext, acks: Option<&AckTracker>) {
+        let Some(deadline) = context.drain_deadline else {
+            return;
+        };
+        let outstanding = acks.map_or(0, AckTracker::outstanding);
+        let overrun = Instant::now().saturating_duration_since(deadline);
+        if outstanding == 0 && overrun.is_zero() {
+            return;
+        }
+        eprintln!(
+            "shutdown drain missed its deadline by {overrun:?} with {outstanding} records outstanding"
+        );
+        if let Some(on_missed) = &self.on_missed {
+            on_missed(&MissedDrain {
+                outstanding,
+                overrun,
+            });
+        }
+    }
+}
+
+impl fmt::Debug for ShutdownDrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownDrain")
+            .field("deadline", &self.deadline)
+            .field("poll_interval", &self.poll_interval)
+            .field("on_missed", &self.on_missed.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::Record;
+
+    fn draining(deadline: Instant) -> ShardContext {
+        ShardContext {
+            drain_deadline: Some(deadline),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn polls_while_records_are_outstanding() {
+        let drain = ShutdownDrain::new(Duration::from_secs(1));
+        let context = draining(Instant::now() + Duration::from_secs(1));
+        let tracker = AckTracker::new();
+        let handle = tracker.track(&Record {
+            raw_data: vec![],
+            partition_key: "1".to_string(),
+            explicit_hash_key: None,
+            sequence_number: "1".parse().unwrap(),
+            approximate_arrival_timestamp: 0.0,
+            extra: Default::default(),
+        });
+
+        assert_eq!(
+            drain.next_poll(&context, Some(&tracker)),
+            Some(drain.poll_interval)
+        );
+        handle.ack();
+        assert_eq!(drain.next_poll(&context, Some(&tracker)), None);
+        assert_eq!(drain.next_poll(&context, None), None);
+    }
+
+    #[test]
+    fn stops_polling_at_deadline() {
+        let drain = ShutdownDrain::new(Duration::ZERO);
+        let context = draining(Instant::now());
+
+        assert_eq!(drain.next_poll(&context, None), None);
+        assert_eq!(drain.next_poll(&ShardContext::default(), None), None);
+    }
+}
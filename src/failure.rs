@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::backoff::Backoff;
+
+/// Decides what the runner does when a [`Processor`](crate::Processor) callback returns an error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Calls the failed callback again with the same input, waiting between attempts.
+    /// Once `max_attempts` have failed, the process exits with `exit_code`.
+    Retry {
+        max_attempts: u32,
+        backoff: Backoff,
+        exit_code: i32,
+    },
+    /// Logs the error and acknowledges the message as if the callback had succeeded.
+    Skip,
+    /// Exits the process with the given code, so the daemon restarts the processor.
+    Exit { code: i32 },
+}
+
+impl Default for FailurePolicy {
+    fn default() -> Self {
+        FailurePolicy::Exit { code: 1 }
+    }
+}
+
+impl FailurePolicy {
+    /// Logs the failed `attempt` at handling `action` and decides what to do next: `Some` delay
+    /// before retrying, `None` to skip the message, or a [`ProcessorFailure`] to exit with.
+    pub(crate) fn on_failure(
+        &self,
+        action: &str,
+        attempt: u32,
+        error: eyre::Report,
+    ) -> Result<Option<Duration>, ProcessorFailure> {
+        match self {
+            FailurePolicy::Retry {
+                max_attempts,
+                backoff,
+                ..
+            } if attempt < *max_attempts => {
+                let delay = backoff.delay(attempt);
+                eprintln!(
+                    "processor failed handling {action} (attempt {attempt}), retrying in {delay:?}: {error:?}"
+                );
+                Ok(Some(delay))
+            }
+            FailurePolicy::Skip => {
+                eprintln!("processor failed handling {action}, skipping: {error:?}");
+                Ok(None)
+            }
+            FailurePolicy::Retry { exit_code, .. } | FailurePolicy::Exit { code: exit_code } => {
+                Err(ProcessorFailure {
+                    action: action.to_string(),
+                    exit_code: *exit_code,
+                    error,
+                })
+            }
+        }
+    }
+}
+
+/// Decides what the runner does when a shard has ended without the processor checkpointing it at
+/// its end, e.g. because [`Processor::shard_ended`](crate::Processor::shard_ended) failed and the
+/// [`FailurePolicy`] skipped it, or returned a [`ShardEndCheckpoint`](crate::checkpointer::ShardEndCheckpoint)
+/// without checkpointing the shard. The daemon does not process the child shards until it is.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ShardEndPolicy {
+    /// Logs the missed checkpoint and checkpoints the shard at its end on behalf of the processor.
+    /// While records tracked by an [`AckTracker`](crate::AckTracker) are still outstanding, it only
+    /// checkpoints up to the acknowledged ones instead, so the child shards are not processed yet.
+    #[default]
+    Checkpoint,
+    /// Only logs the missed checkpoint.
+    Warn,
+    /// Exits the process with the given code before acknowledging the message, so the daemon
+    /// restarts the processor.
+    Exit { code: i32 },
+}
+
+impl ShardEndPolicy {
+    /// Logs the missed shard end checkpoint and decides whether the runner should make it, given
+    /// the number of `outstanding` records, or returns the [`ProcessorFailure`] to exit with.
+    pub(crate) fn on_missing_checkpoint(
+        &self,
+        outstanding: usize,
+    ) -> Result<bool, ProcessorFailure> {
+        match self {
+            ShardEndPolicy::Checkpoint if outstanding == 0 => {
+                eprintln!(
+                    "processor did not checkpoint the ended shard, checkpointing it at its end"
+                );
+                Ok(true)
+            }
+            ShardEndPolicy::Checkpoint => {
+                eprintln!(
+                    "processor did not checkpoint the ended shard, checkpointing it up to the acknowledged \
+                    records instead of at its end with {outstanding} records outstanding, its child shards \
+                    will not be processed"
+                );
+                Ok(true)
+            }
+            ShardEndPolicy::Warn => {
+                eprintln!(
+                    "processor did not checkpoint the ended shard, its child shards will not be processed"
+                );
+                Ok(false)
+            }
+            ShardEndPolicy::Exit { code } => Err(ProcessorFailure {
+                action: "shardEnded".to_string(),
+                exit_code: *code,
+                error: eyre::eyre!("the ended shard was not checkpointed at its end"),
+            }),
+        }
+    }
+}
+
+/// A processor callback failed and the [`FailurePolicy`] requires the process to exit.
+#[derive(Debug, Error)]
+#[error("processor failed handling {action}: {error:?}")]
+pub struct ProcessorFailure {
+    pub action: String,
+    pub exit_code: i32,
+    pub error: eyre::Report,
+}
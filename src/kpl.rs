@@ -0,0 +1,248 @@
+// De-aggregation of records produced by the Kinesis Producer Library (KPL).
+// For the format, see https://github.com/awslabs/amazon-kinesis-producer/blob/master/aggregation-format.md
+use prost::Message as _;
+
+use crate::messages::{Message, Record};
+
+const KPL_MAGIC: [u8; 4] = [0xF3, 0x89, 0x9A, 0xC2];
+const MD5_DIGEST_LEN: usize = 16;
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct AggregatedRecord {
+    #[prost(string, repeated, tag = "1")]
+    partition_key_table: Vec<String>,
+    #[prost(string, repeated, tag = "2")]
+    explicit_hash_key_table: Vec<String>,
+    #[prost(message, repeated, tag = "3")]
+    records: Vec<AggregatedSubRecord>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct AggregatedSubRecord {
+    #[prost(uint64, required, tag = "1")]
+    partition_key_index: u64,
+    #[prost(uint64, optional, tag = "2")]
+    explicit_hash_key_index: Option<u64>,
+    #[prost(bytes = "vec", required, tag = "3")]
+    data: Vec<u8>,
+}
+
+/// Expands any KPL aggregated records delivered by a `processRecords` message.
+pub(crate) fn deaggregate_message(message: Message) -> Message {
+    match message {
+        Message::ProcessRecords(mut input) => {
+            input.records = deaggregate(&input.records);
+            Message::ProcessRecords(input)
+        }
+        message => message,
+    }
+}
+
+/// Expands any KPL aggregated records into the user records they contain.
+///
+/// Records that are not aggregated, or whose aggregation envelope cannot be verified, are passed
+/// through unchanged, matching the behaviour of the Java KCL.
+pub(crate) fn deaggregate(records: &[Record]) -> Vec<Record> {
+    let mut result = Vec::with_capacity(records.len());
+    for record in records {
+        match deaggregate_record(record) {
+            Some(sub_records) => result.extend(sub_records),
+            None => result.push(record.clone()),
+        }
+    }
+
+    result
+}
+
+fn deaggregate_record(record: &Record) -> Option<Vec<Record>> {
+    let payload = aggregated_payload(&record.raw_data)?;
+    let aggregated = AggregatedRecord::decode(payload).ok()?;
+
+    aggregated
+        .records
+        .into_iter()
+        .enumerate()
+        .map(|(index, sub_record)| {
+            let partition_key = lookup(
+                &aggregated.partition_key_table,
+                sub_record.partition_key_index,
+            )?;
+            let explicit_hash_key = match sub_record.explicit_hash_key_index {
+                Some(index) => Some(lookup(&aggregated.explicit_hash_key_table, index)?.clone()),
+                None => None,
+            };
+
+            Some(Record {
+                raw_data: sub_record.data,
+                partition_key: partition_key.clone(),
+                explicit_hash_key,
+                sequence_number: record
+                    .sequence_number
+                    .with_sub_sequence_number(index as u64),
+                approximate_arrival_timestamp: record.approximate_arrival_timestamp,
+                extra: record.extra.clone(),
+            })
+        })
+        .collect()
+}
+
+fn lookup(table: &[String], index: u64) -> Option<&String> {
+    table.get(usize::try_from(index).ok()?)
+}
+
+/// Returns the protobuf payload if `data` carries the KPL magic header and a matching MD5 trailer.
+fn aggregated_payload(data: &[u8]) -> Option<&[u8]> {
+    let body = data.strip_prefix(&KPL_MAGIC)?;
+    if body.len() < MD5_DIGEST_LEN {
+        return None;
+    }
+    let (payload, digest) = body.split_at(body.len() - MD5_DIGEST_LEN);
+    if md5::compute(payload).0 != digest {
+        return None;
+    }
+
+    Some(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(raw_data: Vec<u8>) -> Record {
+        Record {
+            raw_data,
+            partition_key: "parent".to_string(),
+            explicit_hash_key: None,
+            sequence_number: "49590338271490256608559692538361571095921575989136588898"
+                .parse()
+                .unwrap(),
+            approximate_arrival_timestamp: 1570887011763.01,
+            extra: Default::default(),
+        }
+    }
+
+    fn aggregate(aggregated: &AggregatedRecord) -> Vec<u8> {
+        let payload = aggregated.encode_to_vec();
+        let mut data = KPL_MAGIC.to_vec();
+        data.extend_from_slice(&payload);
+        data.extend_from_slice(&md5::compute(&payload).0);
+
+        data
+    }
+
+    fn sub_record(partition_key_index: u64, data: &str) -> AggregatedSubRecord {
+        AggregatedSubRecord {
+            partition_key_index,
+            explicit_hash_key_index: None,
+            data: data.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn deaggregate_aggregated_record() {
+        let aggregated = AggregatedRecord {
+            partition_key_table: vec!["a".to_string(), "b".to_string()],
+            explicit_hash_key_table: vec![],
+            records: vec![sub_record(0, "first"), sub_record(1, "second")],
+        };
+        let given = record(aggregate(&aggregated));
+
+        let actual = deaggregate(std::slice::from_ref(&given));
+
+        assert_eq!(actual.len(), 2);
+        assert_eq!(actual[0].raw_data, b"first");
+        assert_eq!(actual[0].partition_key, "a");
+        assert_eq!(actual[0].sequence_number.sub_sequence_number(), 0);
+        assert_eq!(actual[1].raw_data, b"second");
+        assert_eq!(actual[1].partition_key, "b");
+        assert_eq!(actual[1].sequence_number.sub_sequence_number(), 1);
+        for sub_record in actual {
+            assert_eq!(
+                sub_record.sequence_number.sequence_number(),
+                given.sequence_number.sequence_number()
+            );
+            assert_eq!(
+                sub_record.approximate_arrival_timestamp,
+                given.approximate_arrival_timestamp
+            );
+        }
+    }
+
+    #[test]
+    fn deaggregate_resolves_explicit_hash_keys() {
+        let aggregated = AggregatedRecord {
+            partition_key_table: vec!["a".to_string()],
+            explicit_hash_key_table: vec!["123".to_string(), "456".to_string()],
+            records: vec![
+                AggregatedSubRecord {
+                    explicit_hash_key_index: Some(1),
+                    ..sub_record(0, "first")
+                },
+                sub_record(0, "second"),
+            ],
+        };
+        let given = record(aggregate(&aggregated));
+
+        let actual = deaggregate(std::slice::from_ref(&given));
+
+        assert_eq!(actual[0].explicit_hash_key.as_deref(), Some("456"));
+        assert_eq!(actual[1].explicit_hash_key, None);
+    }
+
+    #[test]
+    fn deaggregate_passes_through_plain_record() {
+        let given = record(b"Hello, this is a test.".to_vec());
+
+        let actual = deaggregate(std::slice::from_ref(&given));
+
+        assert_eq!(actual, vec![given]);
+    }
+
+    #[test]
+    fn deaggregate_passes_through_record_with_bad_checksum() {
+        let aggregated = AggregatedRecord {
+            partition_key_table: vec!["a".to_string()],
+            explicit_hash_key_table: vec![],
+            records: vec![sub_record(0, "first")],
+        };
+        let mut data = aggregate(&aggregated);
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        let given = record(data);
+
+        let actual = deaggregate(std::slice::from_ref(&given));
+
+        assert_eq!(actual, vec![given]);
+    }
+
+    #[test]
+    fn deaggregate_passes_through_record_with_unknown_partition_key() {
+        let aggregated = AggregatedRecord {
+            partition_key_table: vec!["a".to_string()],
+            explicit_hash_key_table: vec![],
+            records: vec![sub_record(3, "first")],
+        };
+        let given = record(aggregate(&aggregated));
+
+        let actual = deaggregate(std::slice::from_ref(&given));
+
+        assert_eq!(actual, vec![given]);
+    }
+
+    #[test]
+    fn deaggregate_passes_through_record_with_unknown_explicit_hash_key() {
+        let aggregated = AggregatedRecord {
+            partition_key_table: vec!["a".to_string()],
+            explicit_hash_key_table: vec![],
+            records: vec![AggregatedSubRecord {
+                explicit_hash_key_index: Some(0),
+                ..sub_record(0, "first")
+            }],
+        };
+        let given = record(aggregate(&aggregated));
+
+        let actual = deaggregate(std::slice::from_ref(&given));
+
+        assert_eq!(actual, vec![given]);
+    }
+}
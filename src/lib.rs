@@ -1,13 +1,53 @@
 #![doc = include_str!("../README.md")]
+pub(crate) mod ack;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub(crate) mod backoff;
 pub mod checkpointer;
+pub mod codec;
 
+pub(crate) mod context;
+pub(crate) mod drain;
+pub(crate) mod failure;
+#[cfg(feature = "kpl")]
+pub(crate) mod kpl;
+pub(crate) mod lifecycle;
 pub(crate) mod messages;
 pub(crate) mod processor;
 pub mod reader;
 pub(crate) mod responses;
+pub(crate) mod retry;
 mod runner;
+pub(crate) mod sequence;
+pub(crate) mod strategy;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub(crate) mod typed;
+pub(crate) mod watchdog;
 pub mod writer;
 
-pub use messages::Record;
+pub use ack::{AckHandle, AckTracker};
+pub use backoff::Backoff;
+pub use context::{CheckpointProgress, ShardContext};
+pub use drain::{DrainHook, MissedDrain, ShutdownDrain};
+pub use failure::{FailurePolicy, ProcessorFailure, ShardEndPolicy};
+#[cfg(feature = "macros")]
+pub use kcl_macros::processor;
+pub use lifecycle::{LifecycleViolation, ShardState};
+pub use messages::{InitializationInput, ParseMode, ProcessRecordsInput, Record};
 pub use processor::Processor;
-pub use runner::{run, tick};
+pub use retry::{CheckpointAttempt, CheckpointRetryHook, CheckpointRetryPolicy};
+pub use runner::{run, tick, RunError, Runner, RunnerBuilder, TickOutcome};
+pub use sequence::{ExtendedSequenceNumber, InvalidSequenceNumber};
+pub use strategy::{CheckpointEvery, CheckpointStrategy, EveryBatch};
+pub use typed::{
+    DeadLetterSink, RecordMetadata, Typed, TypedBatch, TypedProcessor, Undecodable,
+    UndecodablePolicy,
+};
+
+/// Items used by the code generated by [`processor`], not part of the public API.
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub mod __private {
+    pub use eyre::Report;
+}
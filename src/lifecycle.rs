@@ -0,0 +1,148 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::messages::Message;
+
+/// Where a shard is in the lifecycle the MultiLangDaemon drives processors through.
+///
+/// A processor is initialized once, then receives records until it loses its lease or the shard
+/// ends, possibly being asked to shut down in between. Nothing is expected after the lease is lost or
+/// the shard has ended.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShardState {
+    /// Waiting for `initialize`.
+    #[default]
+    Uninitialized,
+    /// Initialized and receiving records.
+    Processing,
+    /// Asked to shut down, possibly still receiving records already in flight.
+    ShutdownRequested,
+    /// The lease was lost to another worker, checkpointing is no longer possible.
+    LeaseLost,
+    /// The shard has ended and must be checkpointed at its end.
+    ShardEnded,
+}
+
+impl ShardState {
+    /// Returns the state after receiving `message`, or the violation if it is not expected now.
+    pub(crate) fn on_message(self, message: &Message) -> Result<ShardState, LifecycleViolation> {
+        use ShardState::*;
+
+        let next = match (self, message) {
+            (_, Message::Unknown(_)) => Some(self),
+            (Uninitialized, Message::Initialize(_)) => Some(Processing),
+            (Processing | ShutdownRequested, Message::ProcessRecords(_)) => Some(self),
+            (Processing | ShutdownRequested, Message::ShutdownRequested(_)) => {
+                Some(ShutdownRequested)
+            }
+            (Processing | ShutdownRequested, Message::LeaseLost(_)) => Some(LeaseLost),
+            (Processing | ShutdownRequested, Message::ShardEnded(_)) => Some(ShardEnded),
+            _ => None,
+        };
+
+        next.ok_or_else(|| LifecycleViolation {
+            state: self,
+            action: message.action().to_string(),
+        })
+    }
+
+    /// Whether the processor may checkpoint in this state.
+    pub fn allows_checkpoint(self) -> bool {
+        matches!(
+            self,
+            ShardState::Processing | ShardState::ShutdownRequested | ShardState::ShardEnded
+        )
+    }
+}
+
+impl fmt::Display for ShardState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ShardState::Uninitialized => "uninitialized",
+            ShardState::Processing => "processing",
+            ShardState::ShutdownRequested => "shutting down",
+            ShardState::LeaseLost => "no longer leased",
+            ShardState::ShardEnded => "ended",
+        })
+    }
+}
+
+/// The daemon sent a message the shard's lifecycle does not allow in its current state.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("unexpected {action} while the shard is {state}")]
+pub struct LifecycleViolation {
+    pub state: ShardState,
+    pub action: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{parse_message, ParseMode};
+
+    fn message(action: &str) -> Message {
+        let payload = match action {
+            "initialize" => "{\"action\": \"initialize\", \"shardId\": \"shard1\"}".to_string(),
+            "processRecords" => "{\"action\": \"processRecords\", \"records\": []}".to_string(),
+            "shardEnded" | "shutdownRequested" => {
+                format!("{{\"action\": \"{action}\", \"checkpoint\": \"1\"}}")
+            }
+            _ => format!("{{\"action\": \"{action}\"}}"),
+        };
+
+        parse_message(&payload, ParseMode::Lenient).unwrap()
+    }
+
+    fn run(actions: &[&str]) -> Result<ShardState, LifecycleViolation> {
+        actions
+            .iter()
+            .try_fold(ShardState::default(), |state, action| {
+                state.on_message(&message(action))
+            })
+    }
+
+    #[test]
+    fn follows_lifecycle() {
+        assert_eq!(
+            run(&[
+                "initialize",
+                "processRecords",
+                "processRecords",
+                "leaseLost"
+            ]),
+            Ok(ShardState::LeaseLost)
+        );
+        assert_eq!(
+            run(&[
+                "initialize",
+                "shutdownRequested",
+                "processRecords",
+                "shardEnded"
+            ]),
+            Ok(ShardState::ShardEnded)
+        );
+        assert_eq!(
+            run(&["initialize", "leaseTransferred"]),
+            Ok(ShardState::Processing)
+        );
+    }
+
+    #[test]
+    fn rejects_records_before_initialize() {
+        assert_eq!(
+            run(&["processRecords"]),
+            Err(LifecycleViolation {
+                state: ShardState::Uninitialized,
+                action: "processRecords".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_messages_after_end() {
+        assert!(run(&["initialize", "leaseLost", "processRecords"]).is_err());
+        assert!(run(&["initialize", "shardEnded", "shutdownRequested"]).is_err());
+        assert!(run(&["initialize", "initialize"]).is_err());
+    }
+}
@@ -1,35 +1,195 @@
+use std::borrow::Cow;
+
 use base64::STANDARD;
 
 use base64_serde::base64_serde_type;
 use eyre::Result;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_json::Result as JsonResult;
+use serde_json::{Map, Result as JsonResult, Value};
 use thiserror::Error;
 
+use crate::codec::{Codec, Decoder};
+use crate::lifecycle::ShardState;
+use crate::sequence::{ExtendedSequenceNumber, InvalidSequenceNumber};
+
 base64_serde_type!(Base64Standard, STANDARD);
 
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(tag = "action")]
 pub(crate) enum Message {
     #[serde(rename = "initialize")]
-    Initialize(InitPayload),
+    Initialize(InitializationInput),
     #[serde(rename = "processRecords")]
-    ProcessRecords(ProcessRecordPayload),
+    ProcessRecords(ProcessRecordsInput),
     #[serde(rename = "checkpoint")]
     Checkpoint(CheckpointWithErrorPayload),
     #[serde(rename = "leaseLost")]
-    LeaseLost,
+    LeaseLost(LeaseLostPayload),
     #[serde(rename = "shardEnded")]
     ShardEnded(CheckpointPayload),
     #[serde(rename = "shutdownRequested")]
     ShutdownRequested(CheckpointPayload),
+    // An action introduced by a newer daemon than this crate knows about, only parsed leniently.
+    #[serde(skip)]
+    Unknown(UnknownPayload),
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "camelCase")]
-pub(crate) struct InitPayload {
+const KNOWN_ACTIONS: [&str; 6] = [
+    "initialize",
+    "processRecords",
+    "checkpoint",
+    "leaseLost",
+    "shardEnded",
+    "shutdownRequested",
+];
+
+impl Message {
+    pub(crate) fn action(&self) -> &str {
+        match self {
+            Message::Initialize(_) => "initialize",
+            Message::ProcessRecords(_) => "processRecords",
+            Message::Checkpoint(_) => "checkpoint",
+            Message::LeaseLost(_) => "leaseLost",
+            Message::ShardEnded(_) => "shardEnded",
+            Message::ShutdownRequested(_) => "shutdownRequested",
+            Message::Unknown(UnknownPayload { action, .. }) => action,
+        }
+    }
+
+    /// Returns the name of a field that is not part of the known protocol, if there is any.
+    fn unknown_field(&self) -> Option<&str> {
+        let extra = match self {
+            Message::Initialize(payload) => &payload.extra,
+            Message::ProcessRecords(payload) => {
+                return first_key(&payload.extra).or_else(|| {
+                    payload
+                        .records
+                        .iter()
+                        .find_map(|record| first_key(&record.extra))
+                })
+            }
+            Message::Checkpoint(payload) => &payload.extra,
+            Message::LeaseLost(payload) => &payload.extra,
+            Message::ShardEnded(payload) | Message::ShutdownRequested(payload) => &payload.extra,
+            Message::Unknown(payload) => &payload.payload,
+        };
+
+        first_key(extra)
+    }
+}
+
+fn first_key(map: &Map<String, Value>) -> Option<&str> {
+    map.keys().next().map(String::as_str)
+}
+
+/// How strictly messages from the daemon are parsed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Unknown actions are handed to [`Processor::unknown_action`](crate::Processor::unknown_action)
+    /// and unknown fields are preserved, so that a newer daemon does not break the processor.
+    #[default]
+    Lenient,
+    /// Unknown actions and fields are rejected as protocol errors, useful in tests.
+    Strict,
+}
+
+/// The details of the shard a processor is being initialized for.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(from = "WireInitializationInput", into = "WireInitializationInput")]
+pub struct InitializationInput {
     pub shard_id: String,
+    /// The position processing resumes after, either a checkpointed sequence number or a sentinel
+    /// such as `TRIM_HORIZON` or `LATEST` if the shard has not been checkpointed yet.
+    ///
+    /// A position this version of the crate does not recognise, e.g. a sentinel introduced by a
+    /// newer daemon, is left in `extra` as sent, unless parsing with [`ParseMode::Strict`].
+    pub sequence_number: Option<ExtendedSequenceNumber>,
+    /// Fields sent by the daemon that this version of the crate does not know about.
+    pub extra: Map<String, Value>,
+}
+
+// The position is optional as a whole, which serde cannot express for flattened fields.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WireInitializationInput {
+    shard_id: String,
+    sequence_number: Option<String>,
+    sub_sequence_number: Option<u64>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl From<WireInitializationInput> for InitializationInput {
+    fn from(mut wire: WireInitializationInput) -> Self {
+        let sub_sequence_number = wire.sub_sequence_number.unwrap_or_default();
+        let sequence_number = match wire.sequence_number {
+            Some(sequence_number) => {
+                match ExtendedSequenceNumber::new(&sequence_number, sub_sequence_number) {
+                    Ok(position) => Some(position),
+                    Err(_) => {
+                        wire.extra
+                            .insert("sequenceNumber".to_string(), sequence_number.into());
+                        if let Some(sub_sequence_number) = wire.sub_sequence_number {
+                            wire.extra.insert(
+                                "subSequenceNumber".to_string(),
+                                sub_sequence_number.into(),
+                            );
+                        }
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        Self {
+            shard_id: wire.shard_id,
+            sequence_number,
+            extra: wire.extra,
+        }
+    }
+}
+
+impl From<InitializationInput> for WireInitializationInput {
+    fn from(mut input: InitializationInput) -> Self {
+        let (sequence_number, sub_sequence_number) = match &input.sequence_number {
+            Some(position) => (
+                Some(position.sequence_number().to_string()),
+                Some(position.sub_sequence_number()),
+            ),
+            // An unrecognised position is kept in the extra fields as sent
+            None => (
+                input
+                    .extra
+                    .remove("sequenceNumber")
+                    .and_then(|value| value.as_str().map(str::to_string)),
+                input
+                    .extra
+                    .remove("subSequenceNumber")
+                    .and_then(|value| value.as_u64()),
+            ),
+        };
+
+        Self {
+            shard_id: input.shard_id,
+            sequence_number,
+            sub_sequence_number,
+            extra: input.extra,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub(crate) struct LeaseLostPayload {
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct UnknownPayload {
+    pub action: String,
+    pub payload: Map<String, Value>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -38,38 +198,72 @@ pub struct Record {
     #[serde(rename = "data", with = "Base64Standard")]
     pub raw_data: Vec<u8>,
     pub partition_key: String,
-    pub sequence_number: String,
-    pub sub_sequence_number: Option<u64>,
+    /// The hash key the producer set explicitly to route the record to a shard, instead of hashing
+    /// its partition key. Only known for records de-aggregated from a KPL aggregate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explicit_hash_key: Option<String>,
+    /// The position of the record, including its sub sequence number if it was aggregated by KPL.
+    #[serde(flatten)]
+    pub sequence_number: ExtendedSequenceNumber,
     pub approximate_arrival_timestamp: f64,
+    /// Fields sent by the daemon that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl Record {
+    /// Deserializes the payload as JSON, as it is. For compressed payloads, use
+    /// [`decode`](Self::decode) with a [`Decoder`](crate::codec::Decoder) instead.
     pub fn json<T: DeserializeOwned>(&self) -> JsonResult<T> {
         serde_json::from_slice::<T>(self.raw_data.as_slice())
     }
+
+    /// Decodes the payload with `codec`, e.g. a [`Decoder`](crate::codec::Decoder) decompressing
+    /// it first.
+    pub fn decode<T>(&self, codec: &impl Codec<T>) -> Result<T> {
+        codec.decode(&self.raw_data)
+    }
+
+    /// Returns the payload, decompressed if it starts with the magic bytes of an enabled
+    /// [`Compression`](crate::codec::Compression).
+    pub fn decompressed(&self) -> Result<Cow<'_, [u8]>> {
+        Decoder::new().decompress(&self.raw_data)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// A batch of records delivered to the processor.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct ProcessRecordPayload {
+pub struct ProcessRecordsInput {
     pub records: Vec<Record>,
+    /// How far behind the tip of the stream the records were when they were fetched.
+    pub millis_behind_latest: Option<u64>,
+    /// Fields sent by the daemon that this version of the crate does not know about.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct CheckpointPayload {
     checkpoint: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct CheckpointWithErrorPayload {
     pub(crate) checkpoint: Option<String>,
+    #[serde(default)]
+    pub(crate) sub_sequence_number: Option<u64>,
     pub(crate) error: Option<CheckpointError>,
+    #[serde(flatten)]
+    pub(crate) extra: Map<String, Value>,
 }
 // For more info, see https://github.com/awslabs/amazon-kinesis-client/tree/master/amazon-kinesis-client/src/main/java/software/amazon/kinesis/exceptions
-#[derive(Debug, Deserialize, PartialEq, Eq, Error)]
-#[serde(field_identifier)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Error)]
+#[serde(from = "String")]
 pub enum CheckpointError {
     // This is thrown when the Amazon Kinesis Client Library encounters issues talking to its dependencies
     // (e.g. fetching data from Kinesis, DynamoDB table reads/writes, emitting metrics to CloudWatch).
@@ -86,20 +280,48 @@ pub enum CheckpointError {
     #[error("ShutdownException")]
     ShutdownException,
     // The MultiLang daemon sent us something that is not a checkpoint response, while waiting for one.
-    #[serde(skip)]
     #[error("UnexpectedResponse")]
     UnexpectedResponse,
+    // The requested checkpoint is before the last checkpoint or after the last record delivered,
+    // so it was not sent to the MultiLang daemon.
+    #[error("checkpoint at {0} is outside the delivered records")]
+    OutOfRange(ExtendedSequenceNumber),
+    // The shard's lifecycle does not allow checkpointing, e.g. once the lease has been lost,
+    // so it was not sent to the MultiLang daemon.
+    #[error("cannot checkpoint while the shard is {0}")]
+    NotAllowed(ShardState),
     // A catch-all exception for other errors, e,g, the MultiLang daemon sent us an error that is not defined.
     #[error("Exception: \"{0}\"")]
     Exception(String),
 }
 
+// Errors reported by the daemon, by the name of the exception it caught.
+impl From<String> for CheckpointError {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "KinesisClientLibDependencyException" => {
+                CheckpointError::KinesisClientLibDependencyException
+            }
+            "ThrottlingException" => CheckpointError::ThrottlingException,
+            "InvalidStateException" => CheckpointError::InvalidStateException,
+            "ShutdownException" => CheckpointError::ShutdownException,
+            _ => CheckpointError::Exception(name),
+        }
+    }
+}
+
 impl From<eyre::Report> for CheckpointError {
     fn from(e: eyre::Report) -> Self {
         CheckpointError::Exception(e.to_string())
     }
 }
 
+impl From<InvalidSequenceNumber> for CheckpointError {
+    fn from(e: InvalidSequenceNumber) -> Self {
+        CheckpointError::Exception(e.to_string())
+    }
+}
+
 impl From<serde_json::Error> for CheckpointError {
     fn from(e: serde_json::Error) -> Self {
         CheckpointError::Exception(e.to_string())
@@ -116,8 +338,35 @@ impl CheckpointError {
     }
 }
 
-pub(crate) fn parse_message(payload: &str) -> Result<Message> {
-    let message = serde_json::from_str::<Message>(payload)?;
+pub(crate) fn parse_message(payload: &str, mode: ParseMode) -> Result<Message> {
+    let mut fields = serde_json::from_str::<Map<String, Value>>(payload)?;
+    let action = match fields.get("action") {
+        Some(Value::String(action)) => action.clone(),
+        _ => eyre::bail!("message without an action: {}", payload.trim_end()),
+    };
+    if !KNOWN_ACTIONS.contains(&action.as_str()) {
+        if mode == ParseMode::Strict {
+            eyre::bail!("unknown action \"{action}\"");
+        }
+        fields.remove("action");
+        return Ok(Message::Unknown(UnknownPayload {
+            action,
+            payload: fields,
+        }));
+    }
+
+    let message = serde_json::from_value::<Message>(Value::Object(fields))?;
+    if mode == ParseMode::Strict {
+        if let Message::Initialize(input) = &message {
+            if let Some(Value::String(position)) = input.extra.get("sequenceNumber") {
+                return Err(InvalidSequenceNumber(position.clone()).into());
+            }
+        }
+        if let Some(field) = message.unknown_field() {
+            eyre::bail!("unknown field \"{field}\" in {action} message");
+        }
+    }
+
     Ok(message)
 }
 
@@ -134,14 +383,63 @@ mod tests {
     #[test]
     fn parse_initialize() {
         let given = "{\"action\" :\"initialize\", \"shardId\": \"shard1\"}";
-        let expected = Message::Initialize(InitPayload {
+        let expected = Message::Initialize(InitializationInput {
+            shard_id: "shard1".to_string(),
+            sequence_number: None,
+            extra: Map::new(),
+        });
+
+        let actual = parse_message(given, ParseMode::Strict).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn parse_initialize_with_position() {
+        let given = "{\"action\": \"initialize\", \"shardId\": \"shard1\", \
+            \"sequenceNumber\": \"49590338271490256608559692538361571095921575989136588898\", \
+            \"subSequenceNumber\": 3}";
+        let expected = Message::Initialize(InitializationInput {
             shard_id: "shard1".to_string(),
+            sequence_number: Some(
+                ExtendedSequenceNumber::new(
+                    "49590338271490256608559692538361571095921575989136588898",
+                    3,
+                )
+                .unwrap(),
+            ),
+            extra: Map::new(),
         });
 
-        let actual = parse_message(given).unwrap();
+        let actual = parse_message(given, ParseMode::Strict).unwrap();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn parse_initialize_with_unknown_position() {
+        let given = "{\"action\": \"initialize\", \"shardId\": \"shard1\", \
+            \"sequenceNumber\": \"AT_SOME_NEW_POSITION\", \"subSequenceNumber\": 0}";
+
+        let Message::Initialize(input) = parse_message(given, ParseMode::Lenient).unwrap() else {
+            panic!("expected an initialize message");
+        };
+        assert_eq!(input.sequence_number, None);
+        assert_eq!(input.extra["sequenceNumber"], "AT_SOME_NEW_POSITION");
+        assert_eq!(
+            serde_json::to_value(&input).unwrap(),
+            serde_json::json!({
+                "shardId": "shard1",
+                "sequenceNumber": "AT_SOME_NEW_POSITION",
+                "subSequenceNumber": 0,
+            })
+        );
+
+        let error = parse_message(given, ParseMode::Strict).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid sequence number: \"AT_SOME_NEW_POSITION\""
+        );
+    }
+
     #[test]
     fn parse_process_record() {
         let given = "{\"action\": \"processRecords\", \
@@ -150,13 +448,17 @@ mod tests {
             \"partitionKey\": \"1\",\
             \"sequenceNumber\": \"49590338271490256608559692538361571095921575989136588898\",\
             \"approximateArrivalTimestamp\": 1570887011763.01}]}";
-        let parsed = parse_message(given).unwrap();
+        let parsed = parse_message(given, ParseMode::Strict).unwrap();
 
-        if let Message::ProcessRecords(ProcessRecordPayload { records }) = parsed {
+        if let Message::ProcessRecords(ProcessRecordsInput { records, .. }) = parsed {
             assert_eq!(records.len(), 1);
             assert_eq!(
                 records[0].sequence_number,
-                "49590338271490256608559692538361571095921575989136588898"
+                ExtendedSequenceNumber::new(
+                    "49590338271490256608559692538361571095921575989136588898",
+                    0
+                )
+                .unwrap()
             );
             assert_eq!(records[0].approximate_arrival_timestamp, 1570887011763.01);
             // TODO check if we can do this in serde
@@ -169,6 +471,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_process_record_lag() {
+        let given =
+            "{\"action\": \"processRecords\", \"millisBehindLatest\": 1500, \"records\": []}";
+        let parsed = parse_message(given, ParseMode::Strict).unwrap();
+
+        if let Message::ProcessRecords(input) = parsed {
+            assert_eq!(input.millis_behind_latest, Some(1500));
+        } else {
+            panic!("Did not match expected ProcessRecords event.");
+        }
+    }
+
     #[test]
     fn parse_json_process_record() {
         let given = "{\"action\": \"processRecords\", \
@@ -177,9 +492,9 @@ mod tests {
             \"partitionKey\": \"1\",\
             \"sequenceNumber\": \"49590338271490256608559692538361571095921575989136588898\",\
             \"approximateArrivalTimestamp\": 1570887011763.01}]}";
-        let parsed = parse_message(given).unwrap();
+        let parsed = parse_message(given, ParseMode::Strict).unwrap();
 
-        if let Message::ProcessRecords(ProcessRecordPayload { records }) = parsed {
+        if let Message::ProcessRecords(ProcessRecordsInput { records, .. }) = parsed {
             assert_eq!(records.len(), 1);
             let actual = records[0].json::<DummyPayload>().unwrap();
             let expected = DummyPayload {
@@ -196,19 +511,37 @@ mod tests {
         let given = "{\"action\": \"checkpoint\", \"checkpoint\": \"1234\", \"error\": \"InvalidStateException\"}";
         let expected = Message::Checkpoint(CheckpointWithErrorPayload {
             checkpoint: Some("1234".to_string()),
+            sub_sequence_number: None,
             error: Some(InvalidStateException),
+            extra: Map::new(),
         });
 
-        let actual = parse_message(given).unwrap();
+        let actual = parse_message(given, ParseMode::Strict).unwrap();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn parse_checkpoint_with_unknown_error() {
+        let given = "{\"action\": \"checkpoint\", \"checkpoint\": null, \"error\": \"IllegalArgumentException\"}";
+
+        let actual = parse_message(given, ParseMode::Strict).unwrap();
+        match actual {
+            Message::Checkpoint(payload) => assert_eq!(
+                payload.error,
+                Some(CheckpointError::Exception(
+                    "IllegalArgumentException".to_string()
+                ))
+            ),
+            _ => panic!("Did not match expected Checkpoint event."),
+        }
+    }
+
     #[test]
     fn parse_lease_lost() {
         let given = "{\"action\": \"leaseLost\"}";
-        let expected = Message::LeaseLost;
+        let expected = Message::LeaseLost(LeaseLostPayload { extra: Map::new() });
 
-        let actual = parse_message(given).unwrap();
+        let actual = parse_message(given, ParseMode::Strict).unwrap();
         assert_eq!(actual, expected)
     }
 
@@ -217,9 +550,10 @@ mod tests {
         let given = "{\"action\": \"shardEnded\", \"checkpoint\": \"1234\"}";
         let expected = Message::ShardEnded(CheckpointPayload {
             checkpoint: "1234".to_string(),
+            extra: Map::new(),
         });
 
-        let actual = parse_message(given).unwrap();
+        let actual = parse_message(given, ParseMode::Strict).unwrap();
         assert_eq!(actual, expected);
     }
 
@@ -228,9 +562,70 @@ mod tests {
         let given = "{\"action\": \"shutdownRequested\", \"checkpoint\": \"1234\"}";
         let expected = Message::ShutdownRequested(CheckpointPayload {
             checkpoint: "1234".to_string(),
+            extra: Map::new(),
         });
 
-        let actual = parse_message(given).unwrap();
+        let actual = parse_message(given, ParseMode::Strict).unwrap();
         assert_eq!(actual, expected)
     }
+
+    #[test]
+    fn parse_unknown_action_leniently() {
+        let given = "{\"action\": \"leaseTransferred\", \"newOwner\": \"worker2\"}";
+        let mut payload = Map::new();
+        payload.insert("newOwner".to_string(), Value::from("worker2"));
+        let expected = Message::Unknown(UnknownPayload {
+            action: "leaseTransferred".to_string(),
+            payload,
+        });
+
+        let actual = parse_message(given, ParseMode::Lenient).unwrap();
+        assert_eq!(actual, expected);
+        assert_eq!(actual.action(), "leaseTransferred");
+    }
+
+    #[test]
+    fn parse_unknown_action_strictly() {
+        let given = "{\"action\": \"leaseTransferred\", \"newOwner\": \"worker2\"}";
+
+        let actual = parse_message(given, ParseMode::Strict);
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn parse_unknown_fields_leniently() {
+        let given = "{\"action\": \"processRecords\", \
+        \"records\": [{\
+            \"data\": \"eyJmb28iOiAiYmFyIn0=\",\
+            \"partitionKey\": \"1\",\
+            \"sequenceNumber\": \"49590338271490256608559692538361571095921575989136588898\",\
+            \"approximateArrivalTimestamp\": 1570887011763.01,\
+            \"encryptionType\": \"KMS\"}]}";
+        let parsed = parse_message(given, ParseMode::Lenient).unwrap();
+
+        if let Message::ProcessRecords(ProcessRecordsInput { records, .. }) = parsed {
+            assert_eq!(
+                records[0].extra.get("encryptionType"),
+                Some(&Value::from("KMS"))
+            );
+        } else {
+            panic!("Did not match expected ProcessRecords event.");
+        }
+    }
+
+    #[test]
+    fn parse_unknown_fields_strictly() {
+        let given = "{\"action\": \"leaseLost\", \"reason\": \"stolen\"}";
+
+        let actual = parse_message(given, ParseMode::Strict);
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn parse_message_without_action() {
+        let given = "{\"shardId\": \"shard1\"}";
+
+        let actual = parse_message(given, ParseMode::Lenient);
+        assert!(actual.is_err());
+    }
 }
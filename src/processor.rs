@@ -1,12 +1,56 @@
-use crate::checkpointer::Checkpointer;
-use crate::messages::Record;
-use crate::reader::InputReader;
-use crate::writer::OutputWriter;
-
-pub trait Processor<W: OutputWriter, R: InputReader> {
-    fn initialize(&mut self, shard_id: &str);
-    fn process_records(&mut self, data: &[Record], checkpoint: &mut Checkpointer<W, R>);
-    fn lease_lost(&mut self);
-    fn shard_ended(&mut self, checkpoint: &mut Checkpointer<W, R>);
-    fn shutdown_requested(&mut self, checkpoint: &mut Checkpointer<W, R>);
+use eyre::Result;
+use serde_json::{Map, Value};
+
+use crate::checkpointer::{Checkpoint, CheckpointShardEnd, ShardEndCheckpoint};
+use crate::context::ShardContext;
+use crate::messages::{InitializationInput, ProcessRecordsInput};
+
+/// Handles the messages sent by the MultiLangDaemon for a single shard.
+///
+/// Returning an error from a callback hands it to the runner's [`FailurePolicy`](crate::FailurePolicy).
+/// Every callback receives the [`ShardContext`] as updated with the message being handled.
+///
+/// Callbacks checkpoint through the [`Checkpoint`] and [`CheckpointShardEnd`] trait objects, which
+/// hide the transport to the daemon, so the same implementation works with any runner, and
+/// processors can be boxed as `Box<dyn Processor>`.
+pub trait Processor {
+    fn initialize(&mut self, context: &ShardContext, input: &InitializationInput) -> Result<()>;
+    fn process_records(
+        &mut self,
+        context: &ShardContext,
+        input: &ProcessRecordsInput,
+        checkpoint: &mut dyn Checkpoint,
+    ) -> Result<()>;
+    /// Called once another worker has taken the shard over, so checkpointing is no longer possible.
+    fn lease_lost(&mut self, context: &ShardContext) -> Result<()>;
+    /// Called once the shard has ended. Its child shards are only processed once it has been
+    /// checkpointed at its end, so the [`ShardEndCheckpoint`] of doing so must be returned.
+    fn shard_ended(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn CheckpointShardEnd,
+    ) -> Result<ShardEndCheckpoint>;
+    fn shutdown_requested(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn Checkpoint,
+    ) -> Result<()>;
+
+    /// Called when the daemon closes the input channel, before the runner returns.
+    /// No more messages will arrive and checkpointing is no longer possible, so this is the last
+    /// chance to flush any pending work.
+    fn daemon_gone(&mut self, _context: &ShardContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for actions introduced by a newer daemon than this crate knows about, with the
+    /// remaining fields of the message. The message is acknowledged once this returns.
+    fn unknown_action(
+        &mut self,
+        _context: &ShardContext,
+        _action: &str,
+        _payload: &Map<String, Value>,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
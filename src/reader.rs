@@ -3,6 +3,7 @@ use std::io;
 use eyre::Result;
 
 pub trait InputReader {
+    /// Reads the next line sent by the daemon, returning an empty string once the input is closed.
     fn next(&mut self) -> Result<String>;
 }
 
@@ -10,8 +11,14 @@ pub struct StdinReader {
     stdin: io::Stdin,
 }
 
+impl Default for StdinReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StdinReader {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         Self { stdin: io::stdin() }
     }
 }
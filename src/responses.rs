@@ -11,19 +11,9 @@ pub(crate) struct StatusResponse {
 
 impl StatusResponse {
     pub fn for_message(message: Message) -> Self {
-        let response_for = match message {
-            Message::Initialize(_) => "initialize",
-            Message::ProcessRecords(_) => "processRecords",
-            Message::Checkpoint(_) => "checkpoint",
-            Message::LeaseLost => "leaseLost",
-            Message::ShardEnded(_) => "shardEnded",
-            Message::ShutdownRequested(_) => "shutdownRequested",
-        }
-        .to_string();
-
         Self {
             action: "status".to_string(),
-            response_for,
+            response_for: message.action().to_string(),
         }
     }
 }
@@ -0,0 +1,189 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::backoff::Backoff;
+use crate::messages::CheckpointError;
+
+/// Called for every failed checkpoint attempt, see [`CheckpointRetryPolicy::on_attempt`].
+pub type CheckpointRetryHook = Arc<dyn Fn(&CheckpointAttempt) + Send + Sync>;
+
+/// How a checkpointer retries checkpoints that failed with a
+/// [retryable](CheckpointError::is_retryable) error, before returning the error to the processor.
+#[derive(Clone)]
+pub struct CheckpointRetryPolicy {
+    /// The number of attempts, including the first, before giving up.
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+    /// Waits a random delay between half and all of the backoff delay, so that processors
+    /// throttled together do not retry together.
+    pub jitter: bool,
+    /// Gives up once retrying would take longer than this since the first attempt.
+    pub deadline: Option<Duration>,
+    /// Called for every failed attempt, e.g. to log or count throttled checkpoints.
+    pub on_attempt: Option<CheckpointRetryHook>,
+}
+
+/// A failed checkpoint attempt, as reported to [`CheckpointRetryPolicy::on_attempt`].
+#[derive(Debug)]
+pub struct CheckpointAttempt<'a> {
+    /// The 1-based number of the attempt.
+    pub attempt: u32,
+    pub error: &'a CheckpointError,
+    /// How long until the next attempt, or `None` if the error is returned to the processor.
+    pub retry_in: Option<Duration>,
+}
+
+impl Default for CheckpointRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            backoff: Backoff::default(),
+            jitter: true,
+            deadline: None,
+            on_attempt: None,
+        }
+    }
+}
+
+impl fmt::Debug for CheckpointRetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CheckpointRetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("backoff", &self.backoff)
+            .field("jitter", &self.jitter)
+            .field("deadline", &self.deadline)
+            .field("on_attempt", &self.on_attempt.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl CheckpointRetryPolicy {
+    /// Reports the failed `attempt`, started at `started`, and returns how long to wait before
+    /// retrying, or `None` to give up.
+    pub(crate) fn on_failure(
+        &self,
+        attempt: u32,
+        started: Instant,
+        error: &CheckpointError,
+    ) -> Option<Duration> {
+        let retry_in = Some(self.backoff.delay(attempt))
+            .filter(|_| error.is_retryable() && attempt < self.max_attempts)
+            .map(|delay| if self.jitter { jitter(delay) } else { delay })
+            .filter(|delay| {
+                self.deadline
+                    .is_none_or(|deadline| started.elapsed() + *delay <= deadline)
+            });
+        if let Some(on_attempt) = &self.on_attempt {
+            on_attempt(&CheckpointAttempt {
+                attempt,
+                error,
+                retry_in,
+            });
+        }
+
+        retry_in
+    }
+}
+
+/// Returns a random delay between half and all of `delay`.
+fn jitter(delay: Duration) -> Duration {
+    let half = delay / 2;
+    let nanos = u64::try_from(half.as_nanos()).unwrap_or(u64::MAX);
+
+    half + Duration::from_nanos(random_u64() % nanos.saturating_add(1))
+}
+
+/// Returns a random number, good enough for jitter and seeds but not for cryptography.
+pub(crate) fn random_u64() -> u64 {
+    // Every `RandomState` is keyed differently, from keys randomly seeded once per thread
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn policy() -> CheckpointRetryPolicy {
+        CheckpointRetryPolicy {
+            max_attempts: 3,
+            jitter: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn retries_retryable_errors_with_backoff() {
+        let policy = policy();
+        let started = Instant::now();
+
+        let error = CheckpointError::ThrottlingException;
+        assert_eq!(
+            policy.on_failure(1, started, &error),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            policy.on_failure(2, started, &error),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(policy.on_failure(3, started, &error), None);
+    }
+
+    #[test]
+    fn does_not_retry_other_errors() {
+        let policy = policy();
+
+        let error = CheckpointError::ShutdownException;
+        assert_eq!(policy.on_failure(1, Instant::now(), &error), None);
+    }
+
+    #[test]
+    fn gives_up_at_deadline() {
+        let policy = CheckpointRetryPolicy {
+            deadline: Some(Duration::from_millis(150)),
+            ..policy()
+        };
+        let started = Instant::now();
+
+        let error = CheckpointError::KinesisClientLibDependencyException;
+        assert!(policy.on_failure(1, started, &error).is_some());
+        assert_eq!(policy.on_failure(2, started, &error), None);
+    }
+
+    #[test]
+    fn jitter_stays_within_half_of_delay() {
+        let delay = Duration::from_millis(100);
+        for _ in 0..100 {
+            let jittered = jitter(delay);
+            assert!(jittered >= delay / 2 && jittered <= delay, "{jittered:?}");
+        }
+    }
+
+    #[test]
+    fn reports_every_attempt() {
+        let attempts = Arc::new(Mutex::new(Vec::new()));
+        let reported = attempts.clone();
+        let policy = CheckpointRetryPolicy {
+            on_attempt: Some(Arc::new(move |attempt: &CheckpointAttempt| {
+                reported
+                    .lock()
+                    .unwrap()
+                    .push((attempt.attempt, attempt.retry_in))
+            })),
+            ..policy()
+        };
+        let started = Instant::now();
+
+        let error = CheckpointError::ThrottlingException;
+        policy.on_failure(1, started, &error);
+        policy.on_failure(3, started, &error);
+
+        assert_eq!(
+            *attempts.lock().unwrap(),
+            vec![(1, Some(Duration::from_millis(100))), (3, None)]
+        );
+    }
+}
@@ -1,60 +1,522 @@
-use crate::checkpointer::Checkpointer;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use eyre::Result;
+use crate::ack::AckTracker;
+use crate::checkpointer::{CheckpointRange, CheckpointTarget, Checkpointer, ShardEndCheckpointer};
 
-use crate::messages::{
-    parse_message, CheckpointWithErrorPayload, InitPayload, Message, ProcessRecordPayload,
-};
+use thiserror::Error;
+
+use crate::context::ShardContext;
+use crate::drain::ShutdownDrain;
+use crate::failure::{FailurePolicy, ProcessorFailure, ShardEndPolicy};
+use crate::lifecycle::LifecycleViolation;
+use crate::messages::{parse_message, Message, ParseMode, UnknownPayload};
 use crate::processor::Processor;
 use crate::reader::{InputReader, StdinReader};
 use crate::responses::StatusResponse;
+use crate::retry::CheckpointRetryPolicy;
+use crate::strategy::{due_checkpoint, CheckpointStrategy};
+use crate::watchdog::ParentWatchdog;
 use crate::writer::{write_status, OutputWriter, StdoutWriter};
 
-pub fn run(processor: &mut impl Processor<StdoutWriter, StdinReader>) {
-    let mut reader = StdinReader::new();
-    let mut writer = StdoutWriter::new();
+/// Runs the processor against the MultiLangDaemon over stdin and stdout until the daemon goes away,
+/// then exits the process with the code described in [`RunError::exit_code`].
+pub fn run(processor: &mut (impl Processor + ?Sized)) {
+    let result = Runner::builder().build().run(processor);
+    std::process::exit(exit_code(&result))
+}
+
+/// Reads and handles a single message using the default [`FailurePolicy`].
+///
+/// As no state is kept between calls, the [`ShardState`](crate::ShardState) lifecycle is not
+/// enforced, use a [`Runner`] for that.
+pub fn tick<W: OutputWriter, R: InputReader>(
+    processor: &mut (impl Processor + ?Sized),
+    input_reader: &mut R,
+    output_writer: &mut W,
+) -> Result<TickOutcome, RunError> {
+    tick_with(
+        &RunnerConfig {
+            enforce_lifecycle: false,
+            ..RunnerConfig::default()
+        },
+        &mut ShardContext::default(),
+        processor,
+        input_reader,
+        output_writer,
+    )
+}
+
+/// The result of successfully handling a single message.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TickOutcome {
+    /// A message was handled and acknowledged.
+    Processed,
+    /// The daemon closed the input channel, no more messages will arrive.
+    /// The processor has been notified through [`Processor::daemon_gone`].
+    InputClosed,
+}
+
+/// Why a [`Runner`] stopped before the daemon closed the input channel.
+#[derive(Debug, Error)]
+pub enum RunError {
+    #[error("failed to communicate with the MultiLangDaemon: {0:?}")]
+    Io(eyre::Report),
+    #[error("received an invalid message from the MultiLangDaemon: {0:?}")]
+    Protocol(eyre::Report),
+    #[error("received a message out of order from the MultiLangDaemon: {0}")]
+    Lifecycle(#[from] LifecycleViolation),
+    #[error(transparent)]
+    Processor(#[from] ProcessorFailure),
+}
 
-    loop {
-        tick(processor, &mut reader, &mut writer).unwrap();
+impl RunError {
+    /// The exit code the process should terminate with:
+    ///
+    /// * `0`: the daemon closed the input channel (see [`run`])
+    /// * `2`: reading from or writing to the daemon failed
+    /// * `3`: the daemon sent a message that could not be understood, or was not expected in the
+    ///   shard's [`ShardState`](crate::ShardState)
+    /// * `4`: the daemon exited without closing the input channel (see [`RunnerBuilder::parent_watchdog`])
+    /// * otherwise: the exit code chosen by the [`FailurePolicy`] for a failed callback
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RunError::Io(_) => 2,
+            RunError::Protocol(_) | RunError::Lifecycle(_) => 3,
+            RunError::Processor(failure) => failure.exit_code,
+        }
     }
 }
 
-pub fn tick<W: OutputWriter, R: InputReader>(
-    processor: &mut impl Processor<W, R>,
+pub(crate) fn exit_code(result: &Result<(), RunError>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{e}");
+            e.exit_code()
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct RunnerConfig {
+    pub(crate) failure_policy: FailurePolicy,
+    pub(crate) parent_watchdog: Option<Duration>,
+    pub(crate) parse_mode: ParseMode,
+    pub(crate) checkpoint_retry: Option<CheckpointRetryPolicy>,
+    pub(crate) checkpoint_strategy: Option<Arc<dyn CheckpointStrategy>>,
+    pub(crate) ack_tracker: Option<AckTracker>,
+    pub(crate) enforce_lifecycle: bool,
+    pub(crate) shard_end_policy: ShardEndPolicy,
+    pub(crate) shutdown_drain: Option<ShutdownDrain>,
+}
+
+impl Default for RunnerConfig {
+    fn default() -> Self {
+        Self {
+            failure_policy: FailurePolicy::default(),
+            parent_watchdog: Some(Duration::from_secs(1)),
+            parse_mode: ParseMode::default(),
+            checkpoint_retry: None,
+            checkpoint_strategy: None,
+            ack_tracker: None,
+            enforce_lifecycle: true,
+            shard_end_policy: ShardEndPolicy::default(),
+            shutdown_drain: None,
+        }
+    }
+}
+
+/// Drives a [`Processor`] with messages from an [`InputReader`], answering on an [`OutputWriter`].
+pub struct Runner<R = StdinReader, W = StdoutWriter> {
+    pub(crate) reader: R,
+    pub(crate) writer: W,
+    pub(crate) config: RunnerConfig,
+    pub(crate) context: ShardContext,
+}
+
+impl Runner {
+    /// Creates a builder that talks to the daemon over stdin and stdout unless configured otherwise.
+    pub fn builder() -> RunnerBuilder<StdinReader, StdoutWriter> {
+        RunnerBuilder::new(StdinReader::new, StdoutWriter::new)
+    }
+}
+
+impl<R, W> Runner<R, W> {
+    pub fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// The context of the shard handled so far, as passed to the processor's callbacks.
+    pub fn context(&self) -> &ShardContext {
+        &self.context
+    }
+
+    pub fn into_parts(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+}
+
+impl<R: InputReader, W: OutputWriter> Runner<R, W> {
+    /// Handles messages until the daemon closes the input channel, which returns `Ok` after calling
+    /// [`Processor::daemon_gone`], or until an unrecoverable error occurs.
+    pub fn run(&mut self, processor: &mut (impl Processor + ?Sized)) -> Result<(), RunError> {
+        let _watchdog = self.config.parent_watchdog.map(ParentWatchdog::start);
+        while self.tick(processor)? == TickOutcome::Processed {}
+
+        Ok(())
+    }
+
+    /// Reads and handles a single message.
+    pub fn tick(
+        &mut self,
+        processor: &mut (impl Processor + ?Sized),
+    ) -> Result<TickOutcome, RunError> {
+        tick_with(
+            &self.config,
+            &mut self.context,
+            processor,
+            &mut self.reader,
+            &mut self.writer,
+        )
+    }
+}
+
+/// Builds a [`Runner`], see [`Runner::builder`].
+pub struct RunnerBuilder<R, W> {
+    reader: Io<R>,
+    writer: Io<W>,
+    config: RunnerConfig,
+}
+
+// The default stdin and stdout channels are only opened on build, as opening stdout redirects it.
+enum Io<T> {
+    Default(fn() -> T),
+    Given(T),
+}
+
+impl<T> Io<T> {
+    fn open(self) -> T {
+        match self {
+            Io::Default(open) => open(),
+            Io::Given(io) => io,
+        }
+    }
+}
+
+impl<R, W> RunnerBuilder<R, W> {
+    pub(crate) fn new(reader: fn() -> R, writer: fn() -> W) -> Self {
+        Self {
+            reader: Io::Default(reader),
+            writer: Io::Default(writer),
+            config: RunnerConfig::default(),
+        }
+    }
+
+    /// Sets the channel messages from the daemon are read from.
+    pub fn reader<T>(self, reader: T) -> RunnerBuilder<T, W> {
+        RunnerBuilder {
+            reader: Io::Given(reader),
+            writer: self.writer,
+            config: self.config,
+        }
+    }
+
+    /// Sets the channel responses to the daemon are written to.
+    pub fn writer<T>(self, writer: T) -> RunnerBuilder<R, T> {
+        RunnerBuilder {
+            reader: self.reader,
+            writer: Io::Given(writer),
+            config: self.config,
+        }
+    }
+
+    /// Sets how errors returned from processor callbacks are handled.
+    pub fn failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.config.failure_policy = failure_policy;
+        self
+    }
+
+    /// Sets how often [`Runner::run`] checks whether the daemon, its parent process, is still alive,
+    /// exiting the process if it is not. `None` disables the check. Defaults to every second.
+    pub fn parent_watchdog(mut self, interval: Option<Duration>) -> Self {
+        self.config.parent_watchdog = interval;
+        self
+    }
+
+    /// Sets how strictly messages from the daemon are parsed. Defaults to [`ParseMode::Lenient`].
+    pub fn parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.config.parse_mode = parse_mode;
+        self
+    }
+
+    /// Sets how checkpoints failing with a retryable error are retried before the error is returned
+    /// to the processor. Defaults to not retrying.
+    pub fn checkpoint_retry(mut self, policy: CheckpointRetryPolicy) -> Self {
+        self.config.checkpoint_retry = Some(policy);
+        self
+    }
+
+    /// Lets the runner checkpoint on behalf of the processor, see [`CheckpointStrategy`]. Defaults
+    /// to leaving checkpointing to the processor.
+    pub fn checkpoint_strategy(mut self, strategy: impl CheckpointStrategy + 'static) -> Self {
+        self.config.checkpoint_strategy = Some(Arc::new(strategy));
+        self
+    }
+
+    /// Lets the runner checkpoint at the [watermark](AckTracker::watermark) of records acknowledged
+    /// through the tracker, rather than at the latest delivered record. Without a
+    /// [`checkpoint_strategy`](Self::checkpoint_strategy), the runner checkpoints whenever the
+    /// watermark has advanced.
+    pub fn ack_tracker(mut self, tracker: AckTracker) -> Self {
+        self.config.ack_tracker = Some(tracker);
+        self
+    }
+
+    /// Sets whether messages and checkpoints are checked against the shard's
+    /// [`ShardState`](crate::ShardState), failing with [`RunError::Lifecycle`] and
+    /// [`CheckpointError::NotAllowed`](crate::checkpointer::CheckpointError::NotAllowed)
+    /// respectively. Defaults to `true`.
+    pub fn enforce_lifecycle(mut self, enforce: bool) -> Self {
+        self.config.enforce_lifecycle = enforce;
+        self
+    }
+
+    /// Sets what to do when a shard has ended without the processor checkpointing it at its end.
+    /// Defaults to [`ShardEndPolicy::Checkpoint`].
+    pub fn shard_end_policy(mut self, policy: ShardEndPolicy) -> Self {
+        self.config.shard_end_policy = policy;
+        self
+    }
+
+    /// Gives the processor a deadline to drain by once shutdown is requested, after which the
+    /// runner checkpoints the highest completed position, see [`ShutdownDrain`].
+    pub fn shutdown_drain(mut self, drain: ShutdownDrain) -> Self {
+        self.config.shutdown_drain = Some(drain);
+        self
+    }
+
+    pub fn build(self) -> Runner<R, W> {
+        Runner {
+            reader: self.reader.open(),
+            writer: self.writer.open(),
+            config: self.config,
+            context: ShardContext::default(),
+        }
+    }
+}
+
+fn tick_with<W: OutputWriter, R: InputReader>(
+    config: &RunnerConfig,
+    context: &mut ShardContext,
+    processor: &mut (impl Processor + ?Sized),
     input_reader: &mut R,
     output_writer: &mut W,
-) -> Result<()> {
-    let next = input_reader.next()?;
-    let message = parse_message(&next)?;
+) -> Result<TickOutcome, RunError> {
+    let next = input_reader.next().map_err(RunError::Io)?;
+    let Some(delivery) = Delivery::receive(config, context, &next)? else {
+        with_failure_policy(&config.failure_policy, "daemonGone", || {
+            processor.daemon_gone(context)
+        })?;
+        return Ok(TickOutcome::InputClosed);
+    };
+    let message = delivery.message();
+
+    with_failure_policy(&config.failure_policy, message.action(), || {
+        process_message(
+            config,
+            context,
+            processor,
+            message,
+            output_writer,
+            input_reader,
+        )
+    })?;
+    while let Some(delay) = delivery.next_drain_poll(config, context) {
+        std::thread::sleep(delay);
+    }
+    if let Some(target) = delivery.due_checkpoint(config, context)? {
+        with_failure_policy(&config.failure_policy, message.action(), || {
+            checkpoint_for_processor(
+                config,
+                context,
+                message,
+                target.clone(),
+                output_writer,
+                input_reader,
+            )
+        })?;
+    }
+
+    write_status(output_writer, delivery.into_status()).map_err(RunError::Io)?;
+
+    Ok(TickOutcome::Processed)
+}
+
+/// A message from the daemon on its way through a runner.
+///
+/// Holds the state transitions of handling a message, shared by the sync and async runners, which
+/// only do the I/O and the waiting in between.
+pub(crate) struct Delivery<'c> {
+    message: Message,
+    drain: Option<&'c ShutdownDrain>,
+}
+
+impl<'c> Delivery<'c> {
+    /// Parses a line read from the daemon and updates the context for the message, or returns
+    /// `None` if the daemon closed the input channel.
+    pub(crate) fn receive(
+        config: &'c RunnerConfig,
+        context: &mut ShardContext,
+        line: &str,
+    ) -> Result<Option<Self>, RunError> {
+        if line.is_empty() {
+            return Ok(None);
+        }
+        let message = parse_message(line, config.parse_mode).map_err(RunError::Protocol)?;
+        // This should only be sent in response to a checkpoint message sent to the daemon,
+        // we should never receive it unexpectedly here
+        if let Message::Checkpoint(_) = message {
+            return Err(RunError::Protocol(eyre::eyre!(
+                "unexpected checkpoint: {}",
+                line.trim_end()
+            )));
+        }
+        #[cfg(feature = "kpl")]
+        let message = crate::kpl::deaggregate_message(message);
+        if let Err(violation) = context.on_message(&message) {
+            if config.enforce_lifecycle {
+                return Err(RunError::Lifecycle(violation));
+            }
+            context.record(&message);
+        }
+        let drain = match message {
+            Message::ShutdownRequested(_) => config.shutdown_drain.as_ref(),
+            _ => None,
+        };
+        if let Some(drain) = drain {
+            context
+                .drain_deadline
+                .get_or_insert_with(|| Instant::now() + drain.deadline);
+        }
+
+        Ok(Some(Self { message, drain }))
+    }
+
+    pub(crate) fn message(&self) -> &Message {
+        &self.message
+    }
 
-    process_message(processor, &message, output_writer, input_reader);
+    /// Returns how long to wait for records still outstanding once the processor handled a
+    /// shutdown request, or `None` once done waiting, reporting a missed deadline.
+    pub(crate) fn next_drain_poll(
+        &self,
+        config: &RunnerConfig,
+        context: &ShardContext,
+    ) -> Option<Duration> {
+        let drain = self.drain?;
+        let acks = config.ack_tracker.as_ref();
+        let delay = drain.next_poll(context, acks);
+        if delay.is_none() {
+            drain.finish(context, acks);
+        }
+
+        delay
+    }
+
+    /// Returns where the runner should checkpoint after the processor handled the message, see
+    /// [`due_checkpoint`].
+    pub(crate) fn due_checkpoint(
+        &self,
+        config: &RunnerConfig,
+        context: &ShardContext,
+    ) -> Result<Option<CheckpointTarget<'static>>, ProcessorFailure> {
+        due_checkpoint(config, context, &self.message)
+    }
 
-    let status_message = StatusResponse::for_message(message);
-    write_status(output_writer, status_message)?;
+    /// The status response acknowledging the message.
+    pub(crate) fn into_status(self) -> StatusResponse {
+        StatusResponse::for_message(self.message)
+    }
+}
+
+/// Calls `callback` until it succeeds or `policy` gives up on it.
+fn with_failure_policy(
+    policy: &FailurePolicy,
+    action: &str,
+    mut callback: impl FnMut() -> eyre::Result<()>,
+) -> Result<(), ProcessorFailure> {
+    let mut attempt = 1;
+    while let Err(error) = callback() {
+        match policy.on_failure(action, attempt, error)? {
+            Some(delay) => {
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            None => break,
+        }
+    }
 
     Ok(())
 }
 
 pub(crate) fn process_message<W: OutputWriter, R: InputReader>(
-    processor: &mut impl Processor<W, R>,
+    config: &RunnerConfig,
+    context: &mut ShardContext,
+    processor: &mut (impl Processor + ?Sized),
     message: &Message,
     output_writer: &mut W,
     input_reader: &mut R,
-) {
-    let mut checkpointer = Checkpointer::new(output_writer, input_reader);
-    match message {
-        Message::Initialize(InitPayload { shard_id }) => processor.initialize(shard_id),
-        Message::ProcessRecords(ProcessRecordPayload { records }) => {
-            processor.process_records(records, &mut checkpointer)
-        }
-        Message::LeaseLost => processor.lease_lost(),
-        Message::ShardEnded(_) => processor.shard_ended(&mut checkpointer),
-        Message::ShutdownRequested(_) => processor.shutdown_requested(&mut checkpointer),
-        // This should only be sent in response to a checkpoint message sent to the daemon,
-        // we should never receive it unexpectedly here
-        Message::Checkpoint(CheckpointWithErrorPayload { checkpoint, error }) => panic!(
-            "unexpected checkpoint: {:?}, error: {:?}",
-            checkpoint, error
-        ),
+) -> eyre::Result<()> {
+    let range = CheckpointRange::new(config, context, message);
+    let mut checkpointer = Checkpointer::new(output_writer, input_reader, config, range);
+    let ctx = &*context;
+    let result = match message {
+        Message::Initialize(input) => processor.initialize(ctx, input),
+        Message::ProcessRecords(input) => processor.process_records(ctx, input, &mut checkpointer),
+        Message::LeaseLost(_) => processor.lease_lost(ctx),
+        Message::ShardEnded(_) => {
+            let mut shard_end = ShardEndCheckpointer::new(checkpointer);
+            // The returned checkpoint may come from any checkpointer, so whether this shard was
+            // checkpointed at its end is checked against ours instead, see `due_checkpoint`
+            let result = processor
+                .shard_ended(ctx, &mut shard_end)
+                .map(|_checkpoint| ());
+            checkpointer = shard_end.into_inner();
+            result
+        }
+        Message::ShutdownRequested(_) => processor.shutdown_requested(ctx, &mut checkpointer),
+        Message::Unknown(UnknownPayload { action, payload }) => {
+            processor.unknown_action(ctx, action, payload)
+        }
+        Message::Checkpoint(_) => unreachable!("checkpoint messages are rejected before dispatch"),
+    };
+    if let Some(position) = checkpointer.checkpointed() {
+        context.on_checkpoint(position);
     }
+
+    result
+}
+
+/// Checkpoints as decided by the [`CheckpointStrategy`], after the processor handled `message`.
+fn checkpoint_for_processor<W: OutputWriter, R: InputReader>(
+    config: &RunnerConfig,
+    context: &mut ShardContext,
+    message: &Message,
+    target: CheckpointTarget<'_>,
+    output_writer: &mut W,
+    input_reader: &mut R,
+) -> eyre::Result<()> {
+    let range = CheckpointRange::new(config, context, message);
+    let mut checkpointer = Checkpointer::new(output_writer, input_reader, config, range);
+    checkpointer.checkpoint(target)?;
+    if let Some(position) = checkpointer.checkpointed() {
+        context.on_checkpoint(position);
+    }
+
+    Ok(())
 }
@@ -0,0 +1,234 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A position in a shard: a Kinesis sequence number, plus the index of a user record within a record
+/// aggregated by KPL.
+///
+/// Sequence numbers are arbitrarily large decimal numbers and are compared numerically. The
+/// sentinel positions sent by the daemon order before every sequence number, except
+/// [`SHARD_END`](Self::SHARD_END) which orders after all of them.
+///
+/// Parses from and displays as the sequence number, followed by `:` and the sub sequence number
+/// unless it is zero, e.g. `49590338271490256608559692538361571095921575989136588898:3`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(
+    try_from = "WireExtendedSequenceNumber",
+    into = "WireExtendedSequenceNumber"
+)]
+pub struct ExtendedSequenceNumber {
+    sequence_number: SequenceNumber,
+    sub_sequence_number: u64,
+}
+
+// Variant order is the position order.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum SequenceNumber {
+    TrimHorizon,
+    AtTimestamp,
+    Latest,
+    Number(Digits),
+    ShardEnd,
+}
+
+/// A decimal number without leading zeros, so that comparing lengths first orders it numerically.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Digits(String);
+
+impl Ord for Digits {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.cmp(&other.0))
+    }
+}
+
+impl PartialOrd for Digits {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Returned when a string is neither a decimal sequence number nor a known sentinel.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("invalid sequence number: \"{0}\"")]
+pub struct InvalidSequenceNumber(pub String);
+
+impl ExtendedSequenceNumber {
+    /// Reading from the oldest record in the shard.
+    pub const TRIM_HORIZON: Self = Self::sentinel(SequenceNumber::TrimHorizon);
+    /// Reading from a timestamp configured on the daemon.
+    pub const AT_TIMESTAMP: Self = Self::sentinel(SequenceNumber::AtTimestamp);
+    /// Reading from the tip of the shard.
+    pub const LATEST: Self = Self::sentinel(SequenceNumber::Latest);
+    /// The end of a closed shard, after every record in it.
+    pub const SHARD_END: Self = Self::sentinel(SequenceNumber::ShardEnd);
+
+    const fn sentinel(sequence_number: SequenceNumber) -> Self {
+        Self {
+            sequence_number,
+            sub_sequence_number: 0,
+        }
+    }
+
+    /// Parses a sequence number or sentinel as sent by the daemon.
+    pub fn new(
+        sequence_number: &str,
+        sub_sequence_number: u64,
+    ) -> Result<Self, InvalidSequenceNumber> {
+        let parsed = match sequence_number {
+            "TRIM_HORIZON" => SequenceNumber::TrimHorizon,
+            "AT_TIMESTAMP" => SequenceNumber::AtTimestamp,
+            "LATEST" => SequenceNumber::Latest,
+            "SHARD_END" => SequenceNumber::ShardEnd,
+            digits if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => {
+                let trimmed = digits.trim_start_matches('0');
+                let normalized = if trimmed.is_empty() { "0" } else { trimmed };
+                SequenceNumber::Number(Digits(normalized.to_string()))
+            }
+            _ => return Err(InvalidSequenceNumber(sequence_number.to_string())),
+        };
+
+        Ok(Self {
+            sequence_number: parsed,
+            sub_sequence_number,
+        })
+    }
+
+    /// The same sequence number at a different position within a KPL aggregated record.
+    pub fn with_sub_sequence_number(&self, sub_sequence_number: u64) -> Self {
+        Self {
+            sequence_number: self.sequence_number.clone(),
+            sub_sequence_number,
+        }
+    }
+
+    /// The sequence number, or the name of the sentinel.
+    pub fn sequence_number(&self) -> &str {
+        match &self.sequence_number {
+            SequenceNumber::TrimHorizon => "TRIM_HORIZON",
+            SequenceNumber::AtTimestamp => "AT_TIMESTAMP",
+            SequenceNumber::Latest => "LATEST",
+            SequenceNumber::Number(Digits(digits)) => digits,
+            SequenceNumber::ShardEnd => "SHARD_END",
+        }
+    }
+
+    /// The index of the user record within a KPL aggregated record, zero for plain records.
+    pub fn sub_sequence_number(&self) -> u64 {
+        self.sub_sequence_number
+    }
+
+    /// Whether this is one of the sentinel positions rather than the position of a record.
+    pub fn is_sentinel(&self) -> bool {
+        !matches!(self.sequence_number, SequenceNumber::Number(_))
+    }
+}
+
+impl fmt::Display for ExtendedSequenceNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.sequence_number())?;
+        if self.sub_sequence_number != 0 {
+            write!(f, ":{}", self.sub_sequence_number)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for ExtendedSequenceNumber {
+    type Err = InvalidSequenceNumber;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None => Self::new(s, 0),
+            Some((sequence_number, sub_sequence_number)) => {
+                let sub_sequence_number = sub_sequence_number
+                    .parse()
+                    .map_err(|_| InvalidSequenceNumber(s.to_string()))?;
+                Self::new(sequence_number, sub_sequence_number)
+                    .map_err(|_| InvalidSequenceNumber(s.to_string()))
+            }
+        }
+    }
+}
+
+// The fields as they appear in daemon messages, where the sub sequence number may be missing or null.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WireExtendedSequenceNumber {
+    sequence_number: String,
+    #[serde(default)]
+    sub_sequence_number: Option<u64>,
+}
+
+impl TryFrom<WireExtendedSequenceNumber> for ExtendedSequenceNumber {
+    type Error = InvalidSequenceNumber;
+
+    fn try_from(wire: WireExtendedSequenceNumber) -> Result<Self, Self::Error> {
+        Self::new(
+            &wire.sequence_number,
+            wire.sub_sequence_number.unwrap_or_default(),
+        )
+    }
+}
+
+impl From<ExtendedSequenceNumber> for WireExtendedSequenceNumber {
+    fn from(position: ExtendedSequenceNumber) -> Self {
+        Self {
+            sequence_number: position.sequence_number().to_string(),
+            sub_sequence_number: Some(position.sub_sequence_number),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(s: &str) -> ExtendedSequenceNumber {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn compares_numerically() {
+        assert!(position("9") < position("10"));
+        assert!(position("10") < position("10:1"));
+        assert!(position("10:2") < position("11"));
+        assert_eq!(position("007"), position("7"));
+        assert!(
+            position("49590338271490256608559692538361571095921575989136588898")
+                < position("49590338271490256608559692538361571095921575989136588899")
+        );
+    }
+
+    #[test]
+    fn orders_sentinels_around_records() {
+        assert!(ExtendedSequenceNumber::TRIM_HORIZON < ExtendedSequenceNumber::LATEST);
+        assert!(ExtendedSequenceNumber::LATEST < position("0"));
+        assert!(position("99999999999999999999") < ExtendedSequenceNumber::SHARD_END);
+        assert_eq!(position("SHARD_END"), ExtendedSequenceNumber::SHARD_END);
+    }
+
+    #[test]
+    fn rejects_invalid_sequence_numbers() {
+        for given in ["", "12a", "-1", "latest", "12:", "12:x", ":3"] {
+            assert_eq!(
+                given.parse::<ExtendedSequenceNumber>(),
+                Err(InvalidSequenceNumber(given.to_string())),
+                "{given}"
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_display() {
+        for given in ["123", "123:4", "TRIM_HORIZON"] {
+            assert_eq!(position(given).to_string(), given);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use crate::ack::AckTracker;
+use crate::checkpointer::CheckpointTarget;
+use crate::context::{CheckpointProgress, ShardContext};
+use crate::failure::ProcessorFailure;
+use crate::messages::Message;
+use crate::runner::RunnerConfig;
+use crate::sequence::ExtendedSequenceNumber;
+
+/// Decides when the runner checkpoints on behalf of the processor, see
+/// [`RunnerBuilder::checkpoint_strategy`](crate::RunnerBuilder::checkpoint_strategy).
+///
+/// The strategy is asked after every successfully processed batch of records. Regardless of the
+/// strategy, the runner checkpoints at the latest delivered record when shutdown is requested, unless
+/// the processor has already done so, while the end of a shard is checkpointed by the processor, see
+/// [`CheckpointShardEnd`](crate::checkpointer::CheckpointShardEnd), or as decided by the
+/// [`ShardEndPolicy`](crate::ShardEndPolicy) if it did not. With an
+/// [`AckTracker`](crate::AckTracker), the strategy decides when to checkpoint and the tracker where.
+pub trait CheckpointStrategy: Debug + Send + Sync {
+    /// Whether to checkpoint at the latest delivered record, given the progress since the last
+    /// checkpoint.
+    fn should_checkpoint(&self, progress: &CheckpointProgress) -> bool;
+}
+
+/// Checkpoints after every batch of records.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EveryBatch;
+
+impl CheckpointStrategy for EveryBatch {
+    fn should_checkpoint(&self, _progress: &CheckpointProgress) -> bool {
+        true
+    }
+}
+
+/// Checkpoints once any of the set thresholds has been reached since the last checkpoint.
+///
+/// The interval is only checked when a batch arrives, so an idle shard is not checkpointed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheckpointEvery {
+    pub records: Option<u64>,
+    pub bytes: Option<u64>,
+    pub interval: Option<Duration>,
+}
+
+impl CheckpointStrategy for CheckpointEvery {
+    fn should_checkpoint(&self, progress: &CheckpointProgress) -> bool {
+        let elapsed = progress
+            .since
+            .and_then(|since| since.elapsed().ok())
+            .unwrap_or_default();
+
+        self.records
+            .is_some_and(|records| progress.records >= records)
+            || self.bytes.is_some_and(|bytes| progress.bytes >= bytes)
+            || self.interval.is_some_and(|interval| elapsed >= interval)
+    }
+}
+
+/// Returns where the runner should checkpoint after the processor handled `message`, if anywhere,
+/// or the failure to exit with if the processor did not checkpoint an ended shard at its end.
+pub(crate) fn due_checkpoint(
+    config: &RunnerConfig,
+    context: &ShardContext,
+    message: &Message,
+) -> Result<Option<CheckpointTarget<'static>>, ProcessorFailure> {
+    let strategy = config.checkpoint_strategy.as_ref();
+    let acks = config.ack_tracker.as_ref();
+    let shard_end_missed = matches!(message, Message::ShardEnded(_))
+        && context.last_checkpointed != Some(ExtendedSequenceNumber::SHARD_END);
+    let draining =
+        matches!(message, Message::ShutdownRequested(_)) && config.shutdown_drain.is_some();
+    if strategy.is_none() && acks.is_none() && !shard_end_missed && !draining {
+        return Ok(None);
+    }
+    let latest = match acks {
+        Some(acks) => acks
+            .watermark()
+            .filter(|watermark| context.last_checkpointed.as_ref() < Some(watermark))
+            .map(CheckpointTarget::At),
+        None => (context.since_checkpoint.batches > 0).then_some(CheckpointTarget::LatestDelivered),
+    };
+    let target = match message {
+        Message::ProcessRecords(_) => latest.filter(|_| {
+            strategy.is_none_or(|strategy| strategy.should_checkpoint(&context.since_checkpoint))
+        }),
+        Message::ShardEnded(_) if shard_end_missed => {
+            let outstanding = acks.map_or(0, AckTracker::outstanding);
+            match config.shard_end_policy.on_missing_checkpoint(outstanding)? {
+                true if outstanding == 0 => Some(CheckpointTarget::ShardEnd),
+                // Records still being worked on would be lost once the shard is checkpointed at
+                // its end
+                true => latest,
+                false => None,
+            }
+        }
+        Message::ShutdownRequested(_) => latest,
+        _ => None,
+    };
+
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    #[test]
+    fn checkpoint_every_any_threshold() {
+        let strategy = CheckpointEvery {
+            records: Some(100),
+            bytes: Some(1024),
+            interval: Some(Duration::from_secs(60)),
+        };
+        let progress = |records, bytes, ago| CheckpointProgress {
+            batches: 1,
+            records,
+            bytes,
+            since: Some(SystemTime::now() - Duration::from_secs(ago)),
+        };
+
+        assert!(!strategy.should_checkpoint(&progress(99, 1023, 0)));
+        assert!(strategy.should_checkpoint(&progress(100, 0, 0)));
+        assert!(strategy.should_checkpoint(&progress(0, 1024, 0)));
+        assert!(strategy.should_checkpoint(&progress(0, 0, 61)));
+    }
+
+    #[test]
+    fn checkpoint_every_without_thresholds_never_checkpoints() {
+        let progress = CheckpointProgress {
+            batches: 10,
+            records: 10_000,
+            bytes: 10_000_000,
+            since: Some(SystemTime::UNIX_EPOCH),
+        };
+
+        assert!(!CheckpointEvery::default().should_checkpoint(&progress));
+    }
+}
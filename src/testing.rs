@@ -0,0 +1,37 @@
+//! Helpers for testing processors without a MultiLangDaemon, enabled by the `testing` feature.
+//!
+//! A [`FakeDaemon`] plays a script of messages to a processor through a real [`Runner`](crate::Runner),
+//! answering its checkpoints and recording what it was sent, so tests can assert on the checkpoints
+//! and status responses a processor produced over a whole shard lifecycle. A [`FaultInjector`]
+//! plays the same script with seeded, reproducible faults injected into it.
+//!
+//! To test a processor without a runner, call its callbacks directly with a
+//! [`RecordingCheckpointer`].
+mod daemon;
+mod faults;
+mod recording;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::messages::Record;
+use crate::sequence::ExtendedSequenceNumber;
+
+pub use daemon::{FakeDaemon, FakeReader, FakeWriter};
+pub use faults::{FaultFailure, FaultInjector, FaultReport, Faults, InjectedFault, SEED_VAR};
+pub use recording::{CheckpointCall, RecordingCheckpointer};
+
+/// Builds a record at `sequence_number` holding `data`.
+pub fn record(sequence_number: ExtendedSequenceNumber, data: impl Into<Vec<u8>>) -> Record {
+    let arrived = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    Record {
+        raw_data: data.into(),
+        partition_key: "partition-key".to_string(),
+        explicit_hash_key: None,
+        sequence_number,
+        approximate_arrival_timestamp: arrived.as_secs_f64() * 1000.0,
+        extra: Default::default(),
+    }
+}
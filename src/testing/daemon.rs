@@ -0,0 +1,389 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use serde_json::{json, Value};
+
+use super::faults::{FaultState, InjectedFault};
+use super::record;
+use crate::checkpointer::CheckpointError;
+use crate::messages::Record;
+use crate::processor::Processor;
+use crate::reader::InputReader;
+use crate::runner::{RunError, Runner, RunnerBuilder};
+use crate::sequence::ExtendedSequenceNumber;
+use crate::writer::OutputWriter;
+
+/// A scripted stand-in for the MultiLangDaemon.
+///
+/// Messages are sent in the order they were added, after which the input is closed. Checkpoints
+/// are answered successfully, echoing the checkpointed position, unless failures have been queued
+/// with [`fail_checkpoints`](Self::fail_checkpoints). Clones share their state, so the daemon can
+/// be inspected after handing its [`reader`](Self::reader) and [`writer`](Self::writer) to a
+/// runner.
+#[derive(Clone, Debug, Default)]
+pub struct FakeDaemon {
+    state: Arc<Mutex<DaemonState>>,
+}
+
+#[derive(Debug, Default)]
+struct DaemonState {
+    script: VecDeque<Scripted>,
+    // Answers to checkpoints, sent before the script continues.
+    responses: VecDeque<String>,
+    failures: VecDeque<CheckpointError>,
+    next_sequence_number: u64,
+    // The latest record sent, and whether the shard has ended, as the daemon resolves
+    // checkpoints without a position against them.
+    delivered: Option<ExtendedSequenceNumber>,
+    ended: bool,
+    sent: Vec<String>,
+    statuses: Vec<String>,
+    checkpoints: Vec<ExtendedSequenceNumber>,
+    failed_checkpoints: usize,
+    faults: Option<FaultState>,
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Scripted {
+    pub(super) payload: Value,
+    delivered: Option<ExtendedSequenceNumber>,
+}
+
+impl Scripted {
+    pub(super) fn new(payload: Value) -> Self {
+        Self {
+            payload,
+            delivered: None,
+        }
+    }
+}
+
+impl FakeDaemon {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends `initialize` for `shard_id`, starting from the beginning of the shard.
+    pub fn initialize(self, shard_id: &str) -> Self {
+        self.message(json!({"action": "initialize", "shardId": shard_id}))
+    }
+
+    /// Sends a batch of records holding `data`, at increasing sequence numbers.
+    pub fn records<D: Into<Vec<u8>>>(self, data: impl IntoIterator<Item = D>) -> Self {
+        let records = data
+            .into_iter()
+            .map(|data| {
+                let mut state = self.state();
+                state.next_sequence_number += 1;
+                let sequence_number = state.next_sequence_number.to_string();
+                record(
+                    ExtendedSequenceNumber::new(&sequence_number, 0).unwrap(),
+                    data,
+                )
+            })
+            .collect();
+
+        self.record_batch(records)
+    }
+
+    /// Sends a batch of the given records.
+    pub fn record_batch(self, records: Vec<Record>) -> Self {
+        let delivered = records
+            .iter()
+            .map(|record| &record.sequence_number)
+            .max()
+            .cloned();
+        let payload = json!({
+            "action": "processRecords",
+            "records": records,
+            "millisBehindLatest": 0,
+        });
+        self.state()
+            .script
+            .push_back(Scripted { payload, delivered });
+
+        self
+    }
+
+    pub fn lease_lost(self) -> Self {
+        self.message(json!({"action": "leaseLost"}))
+    }
+
+    pub fn shard_ended(self) -> Self {
+        self.message(json!({"action": "shardEnded", "checkpoint": "SHARD_END"}))
+    }
+
+    pub fn shutdown_requested(self) -> Self {
+        let delivered = self
+            .state()
+            .script
+            .iter()
+            .rev()
+            .find_map(|scripted| scripted.delivered.clone())
+            .unwrap_or(ExtendedSequenceNumber::TRIM_HORIZON);
+
+        self.message(json!({
+            "action": "shutdownRequested",
+            "checkpoint": delivered.sequence_number(),
+        }))
+    }
+
+    /// Sends an arbitrary message, e.g. one this version of the crate does not know about.
+    pub fn message(self, payload: Value) -> Self {
+        self.state().script.push_back(Scripted::new(payload));
+
+        self
+    }
+
+    /// Answers the next checkpoints with `errors`, in order, before succeeding again.
+    /// [`CheckpointError::UnexpectedResponse`] is answered with a message that is not a checkpoint
+    /// response.
+    pub fn fail_checkpoints(self, errors: impl IntoIterator<Item = CheckpointError>) -> Self {
+        self.state().failures.extend(errors);
+
+        self
+    }
+
+    /// The input channel of a runner talking to this daemon.
+    pub fn reader(&self) -> FakeReader {
+        FakeReader {
+            daemon: self.clone(),
+        }
+    }
+
+    /// The output channel of a runner talking to this daemon.
+    pub fn writer(&self) -> FakeWriter {
+        FakeWriter {
+            daemon: self.clone(),
+        }
+    }
+
+    /// A builder for a runner talking to this daemon, to configure before running a processor.
+    pub fn runner(&self) -> RunnerBuilder<FakeReader, FakeWriter> {
+        Runner::builder()
+            .reader(self.reader())
+            .writer(self.writer())
+            .parent_watchdog(None)
+    }
+
+    /// Runs `processor` with a default runner until the script is exhausted.
+    pub fn run(&self, processor: &mut (impl Processor + ?Sized)) -> Result<(), RunError> {
+        self.runner().build().run(processor)
+    }
+
+    /// The actions of the messages sent so far.
+    pub fn sent(&self) -> Vec<String> {
+        self.state().sent.clone()
+    }
+
+    /// The actions the processor acknowledged with a status response, in order.
+    pub fn statuses(&self) -> Vec<String> {
+        self.state().statuses.clone()
+    }
+
+    /// The positions successfully checkpointed, in order.
+    pub fn checkpoints(&self) -> Vec<ExtendedSequenceNumber> {
+        self.state().checkpoints.clone()
+    }
+
+    /// The number of checkpoints answered with an error.
+    pub fn failed_checkpoints(&self) -> usize {
+        self.state().failed_checkpoints
+    }
+
+    /// Asserts that every message sent was acknowledged, in order.
+    #[track_caller]
+    pub fn assert_all_acknowledged(&self) {
+        let state = self.state();
+        assert_eq!(
+            state.statuses, state.sent,
+            "not every message was acknowledged"
+        );
+    }
+
+    /// Asserts that the last successful checkpoint is at `position`.
+    #[track_caller]
+    pub fn assert_checkpointed_at(&self, position: &ExtendedSequenceNumber) {
+        assert_eq!(
+            self.state().checkpoints.last(),
+            Some(position),
+            "unexpected last checkpoint"
+        );
+    }
+
+    /// Asserts that the shard was checkpointed at its end.
+    #[track_caller]
+    pub fn assert_shard_end_checkpointed(&self) {
+        self.assert_checkpointed_at(&ExtendedSequenceNumber::SHARD_END);
+    }
+
+    /// Injects `faults` into the rest of the script and the checkpoints answered.
+    pub(super) fn inject(&self, faults: FaultState) {
+        self.state().faults = Some(faults);
+    }
+
+    /// The faults injected so far.
+    pub(super) fn injected(&self) -> Vec<InjectedFault> {
+        self.state()
+            .faults
+            .as_ref()
+            .map(|faults| faults.injected.clone())
+            .unwrap_or_default()
+    }
+
+    fn next(&self) -> String {
+        let mut guard = self.state();
+        let state = &mut *guard;
+        if let Some(response) = state.responses.pop_front() {
+            return response;
+        }
+        let live = state.live();
+        let shutdown_requested = state
+            .sent
+            .iter()
+            .any(|action| action == "shutdownRequested");
+        let interruption = match &mut state.faults {
+            Some(faults) if live => faults.interrupt(
+                &mut state.script,
+                shutdown_requested,
+                state.delivered.as_ref(),
+            ),
+            _ => None,
+        };
+        let scripted = match interruption {
+            Some(scripted) => scripted,
+            None => {
+                let Some(scripted) = state.script.pop_front() else {
+                    return String::new();
+                };
+                match &mut state.faults {
+                    Some(faults) => faults.on_send(scripted, &mut state.script),
+                    None => scripted,
+                }
+            }
+        };
+        let action = scripted.payload["action"].as_str().unwrap_or_default();
+        state.ended |= action == "shardEnded";
+        state.sent.push(action.to_string());
+        if let Some(delivered) = scripted.delivered {
+            state.delivered = Some(delivered);
+        }
+
+        format!("{}\n", scripted.payload)
+    }
+
+    fn receive(&self, payload: &[u8]) -> eyre::Result<()> {
+        let message = serde_json::from_slice::<Value>(payload)?;
+        let mut state = self.state();
+        match message["action"].as_str() {
+            Some("status") => {
+                let action = message["responseFor"].as_str().unwrap_or_default();
+                state.statuses.push(action.to_string());
+            }
+            Some("checkpoint") => {
+                let response = state.checkpoint(&message)?;
+                state.responses.push_back(format!("{response}\n"));
+            }
+            _ => eyre::bail!("unexpected message from the processor: {message}"),
+        }
+
+        Ok(())
+    }
+
+    fn state(&self) -> MutexGuard<'_, DaemonState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl DaemonState {
+    /// Whether the shard has been initialized and has neither lost its lease nor ended, so faults
+    /// may interrupt it.
+    fn live(&self) -> bool {
+        let initialized = self.sent.iter().any(|action| action == "initialize");
+        let over = self
+            .sent
+            .iter()
+            .any(|action| action == "leaseLost" || action == "shardEnded");
+
+        initialized && !over
+    }
+
+    /// Answers the checkpoint `message`, recording it if successful.
+    fn checkpoint(&mut self, message: &Value) -> eyre::Result<Value> {
+        let position = match message["sequenceNumber"].as_str() {
+            Some(sequence_number) => Some(ExtendedSequenceNumber::new(
+                sequence_number,
+                message["subSequenceNumber"].as_u64().unwrap_or_default(),
+            )?),
+            None if self.ended => Some(ExtendedSequenceNumber::SHARD_END),
+            None => self.delivered.clone(),
+        };
+        let failure = self
+            .failures
+            .pop_front()
+            .or_else(|| self.faults.as_mut()?.on_checkpoint());
+        let error = match failure {
+            None => None,
+            Some(CheckpointError::UnexpectedResponse) => {
+                self.failed_checkpoints += 1;
+                return Ok(json!({"action": "status", "responseFor": "checkpoint"}));
+            }
+            Some(CheckpointError::Exception(name)) => Some(name),
+            Some(error) => Some(error.to_string()),
+        };
+        if error.is_some() {
+            self.failed_checkpoints += 1;
+            return Ok(json!({"action": "checkpoint", "checkpoint": null, "error": error}));
+        }
+        if let Some(position) = &position {
+            self.checkpoints.push(position.clone());
+        }
+
+        Ok(json!({
+            "action": "checkpoint",
+            "checkpoint": position.as_ref().map(ExtendedSequenceNumber::sequence_number),
+            "subSequenceNumber": position.as_ref().map(ExtendedSequenceNumber::sub_sequence_number),
+            "error": null,
+        }))
+    }
+}
+
+/// Reads the messages a [`FakeDaemon`] sends.
+#[derive(Clone, Debug)]
+pub struct FakeReader {
+    daemon: FakeDaemon,
+}
+
+impl InputReader for FakeReader {
+    fn next(&mut self) -> eyre::Result<String> {
+        Ok(self.daemon.next())
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl crate::asynchronous::reader::AsyncInputReader for FakeReader {
+    async fn next(&mut self) -> eyre::Result<String> {
+        Ok(self.daemon.next())
+    }
+}
+
+/// Writes the processor's messages to a [`FakeDaemon`].
+#[derive(Clone, Debug)]
+pub struct FakeWriter {
+    daemon: FakeDaemon,
+}
+
+impl OutputWriter for FakeWriter {
+    fn write(&mut self, payload: &[u8]) -> eyre::Result<()> {
+        self.daemon.receive(payload)
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl crate::asynchronous::writer::AsyncOutputWriter for FakeWriter {
+    async fn write(&mut self, payload: &[u8]) -> eyre::Result<()> {
+        self.daemon.receive(payload)
+    }
+}
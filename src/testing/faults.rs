@@ -0,0 +1,303 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use serde_json::json;
+use thiserror::Error;
+
+use super::daemon::{FakeDaemon, FakeReader, FakeWriter, Scripted};
+use crate::checkpointer::CheckpointError;
+use crate::processor::Processor;
+use crate::retry::random_u64;
+use crate::runner::{RunError, Runner};
+use crate::sequence::ExtendedSequenceNumber;
+
+/// The environment variable a [`FaultInjector`] takes its seed from, to reproduce a failed run.
+pub const SEED_VAR: &str = "KCL_FAULT_SEED";
+
+/// The faults a [`FaultInjector`] injects, each with the chance of injecting it whenever it could be.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Faults {
+    /// The chance of answering a checkpoint with one of `checkpoint_errors`.
+    pub checkpoint_error_rate: f64,
+    pub checkpoint_errors: Vec<CheckpointError>,
+    /// The chance of sending a batch of records twice in a row.
+    pub duplicate_batch_rate: f64,
+    /// The chance of sending an earlier batch of records again after a batch, as after a failover.
+    pub replay_rate: f64,
+    /// The chance of losing the lease before any message after `initialize`, or once the script is
+    /// exhausted, leaving out the rest of the script.
+    pub lease_loss_rate: f64,
+    /// The chance of requesting a shutdown before any message after `initialize`, or once the
+    /// script is exhausted, unless one has been requested already. The rest of the script follows
+    /// it, as messages already in flight.
+    pub shutdown_rate: f64,
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Self {
+            checkpoint_error_rate: 0.0,
+            checkpoint_errors: vec![
+                CheckpointError::ThrottlingException,
+                CheckpointError::KinesisClientLibDependencyException,
+                CheckpointError::InvalidStateException,
+                CheckpointError::ShutdownException,
+            ],
+            duplicate_batch_rate: 0.0,
+            replay_rate: 0.0,
+            lease_loss_rate: 0.0,
+            shutdown_rate: 0.0,
+        }
+    }
+}
+
+/// A fault injected by a [`FaultInjector`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum InjectedFault {
+    CheckpointError(CheckpointError),
+    DuplicateBatch,
+    ReplayedBatch,
+    LeaseLost,
+    ShutdownRequested,
+}
+
+/// Plays the script of a [`FakeDaemon`] to a processor through a [`Runner`], injecting [`Faults`]
+/// along the way.
+///
+/// The faults are chosen by a seeded generator, so a run is reproduced by its seed. Unless given
+/// one, the injector takes its seed from [`SEED_VAR`], or picks a random one, and logs it.
+#[derive(Clone, Debug)]
+pub struct FaultInjector {
+    faults: Faults,
+    seed: u64,
+}
+
+/// A run of a [`FaultInjector`] that completed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FaultReport {
+    pub seed: u64,
+    /// The faults injected, in order.
+    pub injected: Vec<InjectedFault>,
+}
+
+/// A run of a [`FaultInjector`] that failed, with the seed to reproduce it with.
+#[derive(Debug, Error)]
+#[error("{error} (reproduce with {SEED_VAR}={seed}, after injecting {injected:?})")]
+pub struct FaultFailure {
+    pub seed: u64,
+    pub injected: Vec<InjectedFault>,
+    pub error: RunError,
+}
+
+impl FaultInjector {
+    pub fn new(faults: Faults) -> Self {
+        let seed = std::env::var(SEED_VAR)
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(random_u64);
+
+        Self::with_seed(faults, seed)
+    }
+
+    pub fn with_seed(faults: Faults, seed: u64) -> Self {
+        Self { faults, seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Plays the script of `daemon` to `processor` with a default runner until it is exhausted,
+    /// the processor fails or the injected faults end the shard early.
+    pub fn run(
+        &self,
+        daemon: &FakeDaemon,
+        processor: &mut (impl Processor + ?Sized),
+    ) -> Result<FaultReport, FaultFailure> {
+        self.run_with(daemon, &mut daemon.runner().build(), processor)
+    }
+
+    /// Like [`run`](Self::run), but through `runner`, e.g. one built from
+    /// [`FakeDaemon::runner`] with a checkpoint strategy or shutdown drain.
+    pub fn run_with(
+        &self,
+        daemon: &FakeDaemon,
+        runner: &mut Runner<FakeReader, FakeWriter>,
+        processor: &mut (impl Processor + ?Sized),
+    ) -> Result<FaultReport, FaultFailure> {
+        eprintln!(
+            "injecting faults with seed {seed}, set {SEED_VAR}={seed} to reproduce",
+            seed = self.seed
+        );
+        daemon.inject(FaultState::new(self.faults.clone(), self.seed));
+        let result = runner.run(processor);
+        let injected = daemon.injected();
+
+        match result {
+            Ok(()) => Ok(FaultReport {
+                seed: self.seed,
+                injected,
+            }),
+            Err(error) => Err(FaultFailure {
+                seed: self.seed,
+                injected,
+                error,
+            }),
+        }
+    }
+}
+
+/// The faults a [`FakeDaemon`] injects while sending its script.
+pub(super) struct FaultState {
+    faults: Faults,
+    rng: SplitMix64,
+    // The batches of records sent so far, to replay.
+    sent_batches: Vec<Scripted>,
+    // The number of duplicated or replayed batches at the front of the script, sent as they are.
+    queued: usize,
+    pub(super) injected: Vec<InjectedFault>,
+}
+
+impl FaultState {
+    fn new(faults: Faults, seed: u64) -> Self {
+        Self {
+            faults,
+            rng: SplitMix64(seed),
+            sent_batches: Vec::new(),
+            queued: 0,
+            injected: Vec::new(),
+        }
+    }
+
+    /// Returns the message to send before the rest of `script`, if a fault interrupts the shard
+    /// here. Only called between `initialize` and the shard losing its lease or ending.
+    pub(super) fn interrupt(
+        &mut self,
+        script: &mut VecDeque<Scripted>,
+        shutdown_requested: bool,
+        delivered: Option<&ExtendedSequenceNumber>,
+    ) -> Option<Scripted> {
+        if self.rng.chance(self.faults.lease_loss_rate) {
+            self.injected.push(InjectedFault::LeaseLost);
+            // Nothing follows a lost lease
+            script.clear();
+            self.queued = 0;
+            return Some(Scripted::new(json!({"action": "leaseLost"})));
+        }
+        if !shutdown_requested && self.rng.chance(self.faults.shutdown_rate) {
+            self.injected.push(InjectedFault::ShutdownRequested);
+            let checkpoint = delivered
+                .cloned()
+                .unwrap_or(ExtendedSequenceNumber::TRIM_HORIZON);
+            return Some(Scripted::new(json!({
+                "action": "shutdownRequested",
+                "checkpoint": checkpoint.sequence_number(),
+            })));
+        }
+
+        None
+    }
+
+    /// Returns the message to send for `scripted`, which was up next in `script`, queuing any
+    /// duplicated or replayed batches to follow it.
+    pub(super) fn on_send(
+        &mut self,
+        scripted: Scripted,
+        script: &mut VecDeque<Scripted>,
+    ) -> Scripted {
+        if self.queued > 0 {
+            self.queued -= 1;
+            return scripted;
+        }
+        if scripted.payload["action"] != "processRecords" {
+            return scripted;
+        }
+        if !self.sent_batches.is_empty() && self.rng.chance(self.faults.replay_rate) {
+            self.injected.push(InjectedFault::ReplayedBatch);
+            let replayed = self.rng.below(self.sent_batches.len());
+            script.push_front(self.sent_batches[replayed].clone());
+            self.queued += 1;
+        }
+        if self.rng.chance(self.faults.duplicate_batch_rate) {
+            self.injected.push(InjectedFault::DuplicateBatch);
+            script.push_front(scripted.clone());
+            self.queued += 1;
+        }
+        self.sent_batches.push(scripted.clone());
+
+        scripted
+    }
+
+    /// Returns the error to answer a checkpoint with, if any.
+    pub(super) fn on_checkpoint(&mut self) -> Option<CheckpointError> {
+        let errors = &self.faults.checkpoint_errors;
+        if errors.is_empty() || !self.rng.chance(self.faults.checkpoint_error_rate) {
+            return None;
+        }
+        let error = errors[self.rng.below(errors.len())].clone();
+        self.injected
+            .push(InjectedFault::CheckpointError(error.clone()));
+
+        Some(error)
+    }
+}
+
+impl fmt::Debug for FaultState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FaultState")
+            .field("faults", &self.faults)
+            .field("injected", &self.injected)
+            .finish()
+    }
+}
+
+/// A small, seedable pseudo random generator, see <https://prng.di.unimi.it/splitmix64.c>.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+        z ^ (z >> 31)
+    }
+
+    /// Returns `true` with the chance of `rate`.
+    fn chance(&mut self, rate: f64) -> bool {
+        // The top 53 bits give a uniform float in [0, 1)
+        let sample = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+
+        sample < rate
+    }
+
+    /// Returns a number below `bound`, which must not be zero.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut first = SplitMix64(42);
+        let mut second = SplitMix64(42);
+
+        for _ in 0..100 {
+            assert_eq!(first.next_u64(), second.next_u64());
+        }
+        assert_ne!(SplitMix64(42).next_u64(), SplitMix64(43).next_u64());
+    }
+
+    #[test]
+    fn chance_respects_extremes() {
+        let mut rng = SplitMix64(7);
+
+        assert!((0..1000).all(|_| !rng.chance(0.0)));
+        assert!((0..1000).all(|_| rng.chance(1.0)));
+    }
+}
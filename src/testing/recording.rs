@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+
+use crate::checkpointer::{
+    Checkpoint, CheckpointError, CheckpointShardEnd, CheckpointTarget, ShardEndCheckpoint,
+};
+use crate::sequence::ExtendedSequenceNumber;
+
+/// An in-memory [`Checkpoint`] and [`CheckpointShardEnd`] implementation that records the
+/// checkpoints requested of it, to unit test the callbacks of a processor without a daemon.
+///
+/// Checkpoints succeed and echo the requested position, unless failures have been queued with
+/// [`fail_checkpoints`](Self::fail_checkpoints). Unlike a real
+/// [`Checkpointer`](crate::checkpointer::Checkpointer), positions are not checked against the
+/// delivered records.
+#[derive(Debug, Default)]
+pub struct RecordingCheckpointer {
+    calls: Vec<CheckpointCall>,
+    failures: VecDeque<CheckpointError>,
+}
+
+/// A checkpoint requested of a [`RecordingCheckpointer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckpointCall {
+    /// The requested position, where `None` is the latest delivered record.
+    pub requested: Option<ExtendedSequenceNumber>,
+    /// The error the checkpoint was answered with, if it failed.
+    pub error: Option<CheckpointError>,
+}
+
+impl RecordingCheckpointer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers the next checkpoints with `errors`, in order, before succeeding again.
+    pub fn fail_checkpoints(mut self, errors: impl IntoIterator<Item = CheckpointError>) -> Self {
+        self.failures.extend(errors);
+
+        self
+    }
+
+    /// Every checkpoint requested so far, in order.
+    pub fn calls(&self) -> &[CheckpointCall] {
+        &self.calls
+    }
+
+    /// The positions successfully checkpointed, in order, where `None` is the latest delivered
+    /// record.
+    pub fn checkpointed(&self) -> Vec<Option<&ExtendedSequenceNumber>> {
+        self.calls
+            .iter()
+            .filter(|call| call.error.is_none())
+            .map(|call| call.requested.as_ref())
+            .collect()
+    }
+
+    fn record(
+        &mut self,
+        target: CheckpointTarget<'_>,
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError> {
+        let requested = target.position();
+        let error = self.failures.pop_front();
+        self.calls.push(CheckpointCall {
+            requested: requested.clone(),
+            error: error.clone(),
+        });
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(requested),
+        }
+    }
+}
+
+impl Checkpoint for RecordingCheckpointer {
+    fn checkpoint(
+        &mut self,
+        target: CheckpointTarget<'_>,
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError> {
+        self.record(target)
+    }
+}
+
+impl CheckpointShardEnd for RecordingCheckpointer {
+    fn checkpoint(&mut self) -> Result<ShardEndCheckpoint, CheckpointError> {
+        let echoed = self.record(CheckpointTarget::ShardEnd)?;
+
+        Ok(ShardEndCheckpoint::new(echoed))
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl crate::asynchronous::checkpointer::AsyncCheckpoint for RecordingCheckpointer {
+    async fn checkpoint(
+        &mut self,
+        target: CheckpointTarget<'_>,
+    ) -> Result<Option<ExtendedSequenceNumber>, CheckpointError> {
+        self.record(target)
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl crate::asynchronous::checkpointer::AsyncCheckpointShardEnd for RecordingCheckpointer {
+    async fn checkpoint(&mut self) -> Result<ShardEndCheckpoint, CheckpointError> {
+        CheckpointShardEnd::checkpoint(self)
+    }
+}
@@ -0,0 +1,400 @@
+use std::fmt;
+use std::sync::Arc;
+
+use eyre::Result;
+use serde_json::{Map, Value};
+
+use crate::checkpointer::{Checkpoint, CheckpointShardEnd, ShardEndCheckpoint};
+use crate::codec::{Codec, Json};
+use crate::context::ShardContext;
+use crate::messages::{InitializationInput, ProcessRecordsInput, Record};
+use crate::processor::Processor;
+use crate::sequence::ExtendedSequenceNumber;
+
+/// Called with every record a [`Codec`] could not decode, see [`UndecodablePolicy::DeadLetter`].
+pub type DeadLetterSink = Arc<dyn Fn(&Undecodable) -> Result<()> + Send + Sync>;
+
+/// Handles the messages sent by the MultiLangDaemon for a single shard, like a
+/// [`Processor`](crate::Processor), but receives records already decoded into a `T`.
+///
+/// Wrap it in a [`Typed`] to run it, which decodes every batch with a [`Codec`] and handles the
+/// records that cannot be decoded as configured by its [`UndecodablePolicy`].
+pub trait TypedProcessor<T> {
+    fn initialize(&mut self, context: &ShardContext, input: &InitializationInput) -> Result<()>;
+    /// Handles a decoded batch. Retrying it, as configured by the runner's
+    /// [`FailurePolicy`](crate::FailurePolicy), hands over the same batch without decoding it again.
+    fn process_records(
+        &mut self,
+        context: &ShardContext,
+        batch: &TypedBatch<T>,
+        checkpoint: &mut dyn Checkpoint,
+    ) -> Result<()>;
+    /// See [`Processor::lease_lost`](crate::Processor::lease_lost).
+    fn lease_lost(&mut self, context: &ShardContext) -> Result<()>;
+    /// See [`Processor::shard_ended`](crate::Processor::shard_ended).
+    fn shard_ended(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn CheckpointShardEnd,
+    ) -> Result<ShardEndCheckpoint>;
+    fn shutdown_requested(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn Checkpoint,
+    ) -> Result<()>;
+
+    /// See [`Processor::daemon_gone`](crate::Processor::daemon_gone).
+    fn daemon_gone(&mut self, _context: &ShardContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// See [`Processor::unknown_action`](crate::Processor::unknown_action).
+    fn unknown_action(
+        &mut self,
+        _context: &ShardContext,
+        _action: &str,
+        _payload: &Map<String, Value>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A batch of records, decoded for a [`TypedProcessor`].
+#[derive(Debug)]
+pub struct TypedBatch<T> {
+    /// The decoded records, in the order they were delivered.
+    pub records: Vec<(RecordMetadata, T)>,
+    /// The records that could not be decoded, only kept with [`UndecodablePolicy::Collect`].
+    pub undecodable: Vec<Undecodable>,
+    /// How far behind the tip of the stream the records were when they were fetched.
+    pub millis_behind_latest: Option<u64>,
+}
+
+/// Everything about a [`Record`] but its payload.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordMetadata {
+    pub partition_key: String,
+    /// The hash key the producer set explicitly, see [`Record::explicit_hash_key`].
+    pub explicit_hash_key: Option<String>,
+    pub sequence_number: ExtendedSequenceNumber,
+    pub approximate_arrival_timestamp: f64,
+    /// Fields sent by the daemon that this version of the crate does not know about.
+    pub extra: Map<String, Value>,
+}
+
+impl From<&Record> for RecordMetadata {
+    fn from(record: &Record) -> Self {
+        Self {
+            partition_key: record.partition_key.clone(),
+            explicit_hash_key: record.explicit_hash_key.clone(),
+            sequence_number: record.sequence_number.clone(),
+            approximate_arrival_timestamp: record.approximate_arrival_timestamp,
+            extra: record.extra.clone(),
+        }
+    }
+}
+
+/// A record a [`Codec`] could not decode.
+#[derive(Debug)]
+pub struct Undecodable {
+    pub record: Record,
+    pub error: eyre::Report,
+}
+
+/// Decides what a [`Typed`] processor does with records its [`Codec`] cannot decode.
+#[derive(Clone, Default)]
+pub enum UndecodablePolicy {
+    /// Logs the record and leaves it out of the batch.
+    Skip,
+    /// Leaves the record out of the batch's records and hands it over in
+    /// [`TypedBatch::undecodable`] instead.
+    Collect,
+    /// Hands the record to the sink, e.g. to store it for later inspection, and leaves it out of
+    /// the batch. An error from the sink fails the batch. Records are handed over once per delivery,
+    /// even if the processor is retried.
+    DeadLetter(DeadLetterSink),
+    /// Fails the whole batch without handing it to the processor, leaving it to the runner's
+    /// [`FailurePolicy`](crate::FailurePolicy).
+    #[default]
+    Fail,
+}
+
+impl fmt::Debug for UndecodablePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UndecodablePolicy::Skip => f.write_str("Skip"),
+            UndecodablePolicy::Collect => f.write_str("Collect"),
+            UndecodablePolicy::DeadLetter(_) => f.debug_tuple("DeadLetter").field(&"..").finish(),
+            UndecodablePolicy::Fail => f.write_str("Fail"),
+        }
+    }
+}
+
+/// Runs a [`TypedProcessor`] as a [`Processor`](crate::Processor), decoding the records of every
+/// batch with a [`Codec`], JSON unless configured otherwise.
+pub struct Typed<P, T, C = Json> {
+    processor: P,
+    codec: C,
+    undecodable: UndecodablePolicy,
+    pending: Option<PendingBatch<T>>,
+}
+
+/// A decoded batch the processor has not handled successfully yet, kept so that retrying it neither
+/// decodes it nor hands its undecodable records to a dead-letter sink again.
+struct PendingBatch<T> {
+    positions: Vec<ExtendedSequenceNumber>,
+    batch: TypedBatch<T>,
+}
+
+impl<P, T> Typed<P, T> {
+    pub fn new(processor: P) -> Self {
+        Self {
+            processor,
+            codec: Json,
+            undecodable: UndecodablePolicy::default(),
+            pending: None,
+        }
+    }
+}
+
+impl<P, T, C> Typed<P, T, C> {
+    /// Decodes records with `codec` instead.
+    pub fn codec<D: Codec<T>>(self, codec: D) -> Typed<P, T, D> {
+        Typed {
+            processor: self.processor,
+            codec,
+            undecodable: self.undecodable,
+            pending: None,
+        }
+    }
+
+    /// Handles records that cannot be decoded with `policy`, see [`UndecodablePolicy`].
+    pub fn undecodable(mut self, policy: UndecodablePolicy) -> Self {
+        self.undecodable = policy;
+        self
+    }
+
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    pub fn into_processor(self) -> P {
+        self.processor
+    }
+}
+
+impl<P, T, C: Codec<T>> Typed<P, T, C> {
+    /// Decodes the records of `input`, handling those that cannot be decoded as configured.
+    pub(crate) fn decode(&self, input: &ProcessRecordsInput) -> Result<TypedBatch<T>> {
+        let mut batch = TypedBatch {
+            records: Vec::with_capacity(input.records.len()),
+            undecodable: Vec::new(),
+            millis_behind_latest: input.millis_behind_latest,
+        };
+        for record in &input.records {
+            let error = match self.codec.decode(&record.raw_data) {
+                Ok(decoded) => {
+                    batch.records.push((record.into(), decoded));
+                    continue;
+                }
+                Err(error) => error,
+            };
+            let undecodable = Undecodable {
+                record: record.clone(),
+                error,
+            };
+            match &self.undecodable {
+                UndecodablePolicy::Skip => eprintln!(
+                    "skipping record {} that could not be decoded: {:?}",
+                    record.sequence_number, undecodable.error
+                ),
+                UndecodablePolicy::Collect => batch.undecodable.push(undecodable),
+                UndecodablePolicy::DeadLetter(sink) => sink(&undecodable)?,
+                UndecodablePolicy::Fail => {
+                    return Err(undecodable.error.wrap_err(format!(
+                        "record {} could not be decoded",
+                        record.sequence_number
+                    )))
+                }
+            }
+        }
+
+        Ok(batch)
+    }
+
+    /// Returns the processor with the decoded records of `input`, decoding them unless they are
+    /// still pending from a failed attempt at the same batch.
+    pub(crate) fn decoded(
+        &mut self,
+        input: &ProcessRecordsInput,
+    ) -> Result<(&mut P, &TypedBatch<T>)> {
+        let positions: Vec<_> = input
+            .records
+            .iter()
+            .map(|record| record.sequence_number.clone())
+            .collect();
+        let pending = match self.pending.take() {
+            Some(pending) if pending.positions == positions => pending,
+            _ => PendingBatch {
+                batch: self.decode(input)?,
+                positions,
+            },
+        };
+        let pending = self.pending.insert(pending);
+
+        Ok((&mut self.processor, &pending.batch))
+    }
+
+    /// Drops the pending batch once the processor has handled it.
+    pub(crate) fn handled(&mut self) {
+        self.pending = None;
+    }
+}
+
+impl<P: TypedProcessor<T>, T, C: Codec<T>> Processor for Typed<P, T, C> {
+    fn initialize(&mut self, context: &ShardContext, input: &InitializationInput) -> Result<()> {
+        self.processor.initialize(context, input)
+    }
+
+    fn process_records(
+        &mut self,
+        context: &ShardContext,
+        input: &ProcessRecordsInput,
+        checkpoint: &mut dyn Checkpoint,
+    ) -> Result<()> {
+        let (processor, batch) = self.decoded(input)?;
+        processor.process_records(context, batch, checkpoint)?;
+        self.handled();
+
+        Ok(())
+    }
+
+    fn lease_lost(&mut self, context: &ShardContext) -> Result<()> {
+        self.processor.lease_lost(context)
+    }
+
+    fn shard_ended(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn CheckpointShardEnd,
+    ) -> Result<ShardEndCheckpoint> {
+        self.processor.shard_ended(context, checkpoint)
+    }
+
+    fn shutdown_requested(
+        &mut self,
+        context: &ShardContext,
+        checkpoint: &mut dyn Checkpoint,
+    ) -> Result<()> {
+        self.processor.shutdown_requested(context, checkpoint)
+    }
+
+    fn daemon_gone(&mut self, context: &ShardContext) -> Result<()> {
+        self.processor.daemon_gone(context)
+    }
+
+    fn unknown_action(
+        &mut self,
+        context: &ShardContext,
+        action: &str,
+        payload: &Map<String, Value>,
+    ) -> Result<()> {
+        self.processor.unknown_action(context, action, payload)
+    }
+}
+
+impl<P: fmt::Debug, T, C: fmt::Debug> fmt::Debug for Typed<P, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Typed")
+            .field("processor", &self.processor)
+            .field("codec", &self.codec)
+            .field("undecodable", &self.undecodable)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    fn input(payloads: &[&str]) -> ProcessRecordsInput {
+        let records = payloads
+            .iter()
+            .enumerate()
+            .map(|(i, payload)| Record {
+                raw_data: payload.as_bytes().to_vec(),
+                partition_key: "1".to_string(),
+                explicit_hash_key: None,
+                sequence_number: (i + 1).to_string().parse().unwrap(),
+                approximate_arrival_timestamp: 0.0,
+                extra: Default::default(),
+            })
+            .collect();
+
+        ProcessRecordsInput {
+            records,
+            millis_behind_latest: Some(5),
+            extra: Default::default(),
+        }
+    }
+
+    fn typed(policy: UndecodablePolicy) -> Typed<(), u32> {
+        Typed::new(()).undecodable(policy)
+    }
+
+    #[test]
+    fn decodes_records() {
+        let batch = typed(UndecodablePolicy::Fail)
+            .decode(&input(&["1", "2"]))
+            .unwrap();
+
+        let decoded: Vec<_> = batch.records.iter().map(|(_, value)| *value).collect();
+        assert_eq!(decoded, [1, 2]);
+        assert_eq!(batch.records[1].0.sequence_number, "2".parse().unwrap());
+        assert_eq!(batch.millis_behind_latest, Some(5));
+    }
+
+    #[test]
+    fn keeps_explicit_hash_key() {
+        let mut input = input(&["1"]);
+        input.records[0].explicit_hash_key = Some("123".to_string());
+
+        let batch = typed(UndecodablePolicy::Fail).decode(&input).unwrap();
+
+        assert_eq!(batch.records[0].0.explicit_hash_key.as_deref(), Some("123"));
+    }
+
+    #[test]
+    fn handles_undecodable_records() {
+        let input = input(&["1", "nope", "3"]);
+
+        let skipped = typed(UndecodablePolicy::Skip).decode(&input).unwrap();
+        assert_eq!(skipped.records.len(), 2);
+        assert!(skipped.undecodable.is_empty());
+
+        let collected = typed(UndecodablePolicy::Collect).decode(&input).unwrap();
+        assert_eq!(collected.records.len(), 2);
+        assert_eq!(collected.undecodable[0].record.raw_data, b"nope");
+
+        let dead_letters = Arc::new(Mutex::new(Vec::new()));
+        let sink = dead_letters.clone();
+        let policy = UndecodablePolicy::DeadLetter(Arc::new(move |undecodable: &Undecodable| {
+            sink.lock()
+                .unwrap()
+                .push(undecodable.record.sequence_number.clone());
+            Ok(())
+        }));
+        let routed = typed(policy).decode(&input).unwrap();
+        assert_eq!(routed.records.len(), 2);
+        assert_eq!(*dead_letters.lock().unwrap(), ["2".parse().unwrap()]);
+
+        let error = typed(UndecodablePolicy::Fail).decode(&input).unwrap_err();
+        assert_eq!(error.to_string(), "record 2 could not be decoded");
+    }
+}
@@ -0,0 +1,93 @@
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// The exit code used when the process is orphaned by the MultiLangDaemon.
+pub(crate) const PARENT_GONE_EXIT_CODE: i32 = 4;
+
+/// The parent process as it was when this process started, captured before `main` runs so that a
+/// daemon exiting before the watchdog starts is still noticed. Zero if it could not be captured.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+static PARENT_AT_START: AtomicI32 = AtomicI32::new(0);
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[used]
+#[link_section = ".init_array"]
+static CAPTURE_PARENT_AT_START: extern "C" fn() = {
+    extern "C" fn capture() {
+        // SAFETY: getppid has no preconditions and cannot fail.
+        PARENT_AT_START.store(unsafe { libc::getppid() }, Ordering::Relaxed);
+    }
+    capture
+};
+
+/// The parent process to watch: the one this process was started by where it is known, or else the
+/// current one.
+#[cfg(unix)]
+fn original_parent() -> libc::pid_t {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    match PARENT_AT_START.load(Ordering::Relaxed) {
+        0 => {}
+        parent => return parent,
+    }
+    // SAFETY: getppid has no preconditions and cannot fail.
+    unsafe { libc::getppid() }
+}
+
+/// Exits the process if the parent process (the MultiLangDaemon) dies, for the lifetime of the guard.
+///
+/// The daemon closing stdin is normally noticed by the runner, but the pipe can outlive the daemon
+/// if it is shared with other processes, leaving the processor blocked on a read forever.
+pub(crate) struct ParentWatchdog {
+    stopped: Arc<AtomicBool>,
+}
+
+impl ParentWatchdog {
+    pub(crate) fn start(interval: Duration) -> Self {
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        #[cfg(unix)]
+        {
+            let stopped = stopped.clone();
+            let parent = original_parent();
+            thread::Builder::new()
+                .name("kcl-parent-watchdog".to_string())
+                .spawn(move || {
+                    while !stopped.load(Ordering::Relaxed) {
+                        // SAFETY: getppid has no preconditions and cannot fail.
+                        if unsafe { libc::getppid() } != parent {
+                            eprintln!("parent process {parent} has exited, shutting down");
+                            std::process::exit(PARENT_GONE_EXIT_CODE);
+                        }
+                        thread::sleep(interval);
+                    }
+                })
+                .expect("failed to spawn parent watchdog thread");
+        }
+        #[cfg(not(unix))]
+        let _ = interval;
+
+        Self { stopped }
+    }
+}
+
+impl Drop for ParentWatchdog {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_parent_before_main() {
+        // SAFETY: getppid has no preconditions and cannot fail.
+        assert_eq!(original_parent(), unsafe { libc::getppid() });
+        assert_ne!(PARENT_AT_START.load(Ordering::Relaxed), 0);
+    }
+}
@@ -1,28 +1,48 @@
+use std::fs::File;
 use std::io;
-use std::io::{Stdout, Write};
+use std::io::Write;
+use std::sync::OnceLock;
 
 use crate::responses::StatusResponse;
 use eyre::Result;
 
 pub(crate) fn write_status(writer: &mut impl OutputWriter, message: StatusResponse) -> Result<()> {
-    let mut payload = serde_json::to_vec(&message)?;
-    payload.push(b'\n');
+    let payload = status_payload(message)?;
     writer.write(payload.as_slice())?;
 
     Ok(())
 }
 
+pub(crate) fn status_payload(message: StatusResponse) -> Result<Vec<u8>> {
+    let mut payload = serde_json::to_vec(&message)?;
+    payload.push(b'\n');
+
+    Ok(payload)
+}
+
 pub trait OutputWriter {
     fn write(&mut self, payload: &[u8]) -> Result<()>;
 }
 
 pub struct StdoutWriter {
-    out: Stdout,
+    out: Box<dyn Write + Send>,
+}
+
+impl Default for StdoutWriter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl StdoutWriter {
-    pub(crate) fn new() -> Self {
-        Self { out: io::stdout() }
+    /// Takes exclusive ownership of stdout for the protocol, redirecting any other output to stderr.
+    pub fn new() -> Self {
+        let out: Box<dyn Write + Send> = match protocol_output() {
+            Some(file) => Box::new(file),
+            None => Box::new(io::stdout()),
+        };
+
+        Self { out }
     }
 }
 
@@ -34,3 +54,49 @@ impl OutputWriter for StdoutWriter {
         Ok(())
     }
 }
+
+static PROTOCOL_OUTPUT: OnceLock<Option<File>> = OnceLock::new();
+
+/// Returns the channel the MultiLang protocol is written to.
+///
+/// The first call takes exclusive ownership of the process' stdout: the original file descriptor is
+/// duplicated for the protocol and stdout is pointed at stderr, so that stray prints from the
+/// processor or its dependencies cannot corrupt the protocol stream.
+/// Returns `None` if this is not supported on the platform or the redirection failed, in which case
+/// the protocol is written to the regular stdout.
+pub(crate) fn protocol_output() -> Option<&'static File> {
+    PROTOCOL_OUTPUT
+        .get_or_init(|| match claim_stdout() {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("failed to redirect stdout, stray output may corrupt the protocol: {e}");
+                None
+            }
+        })
+        .as_ref()
+}
+
+#[cfg(unix)]
+fn claim_stdout() -> io::Result<Option<File>> {
+    use std::os::unix::io::FromRawFd;
+
+    io::stdout().flush()?;
+    // SAFETY: these calls only operate on the standard file descriptors and the duplicate we own.
+    unsafe {
+        let fd = libc::fcntl(libc::STDOUT_FILENO, libc::F_DUPFD_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let file = File::from_raw_fd(fd);
+        if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Some(file))
+    }
+}
+
+#[cfg(not(unix))]
+fn claim_stdout() -> io::Result<Option<File>> {
+    Ok(None)
+}
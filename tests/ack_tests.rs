@@ -0,0 +1,97 @@
+mod mocks;
+
+use kcl::{FailurePolicy, Runner, TickOutcome};
+
+use crate::mocks::mock_processor::MockAckingProcessor;
+use crate::mocks::mock_reader::MockReader;
+use crate::mocks::mock_writer::MockWriter;
+use crate::mocks::{checkpoint_at, status, CHECKPOINTED, INITIALIZE};
+
+fn records(sequence_numbers: &[&str]) -> String {
+    let records = sequence_numbers
+        .iter()
+        .map(|sequence_number| {
+            format!(
+                "{{\"data\": \"\", \"partitionKey\": \"1\", \"sequenceNumber\": \"{sequence_number}\", \
+                \"approximateArrivalTimestamp\": 1570887011763.01}}"
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!("{{\"action\": \"processRecords\", \"records\": [{records}]}}")
+}
+
+#[test]
+fn test_checkpoints_contiguous_acks() {
+    let mut reader = MockReader::with_input(INITIALIZE.to_string());
+    reader.add_input(records(&["1", "2", "3"]));
+    reader.add_input(records(&["4"]));
+    reader.add_input(CHECKPOINTED.to_string());
+    reader.add_input("{\"action\": \"shutdownRequested\", \"checkpoint\": \"4\"}".to_string());
+    reader.add_input(CHECKPOINTED.to_string());
+    let mut processor = MockAckingProcessor::default();
+    let mut runner = Runner::builder()
+        .reader(reader)
+        .writer(MockWriter::default())
+        .ack_tracker(processor.tracker.clone())
+        .build();
+
+    runner.tick(&mut processor).unwrap();
+    runner.tick(&mut processor).unwrap();
+    assert_eq!(runner.writer().outputs[1..], [status("processRecords")]);
+
+    let mut handles = processor.handles.drain(..).collect::<Vec<_>>();
+    let third = handles.pop().unwrap();
+    let second = handles.pop().unwrap();
+    third.ack();
+    handles.pop().unwrap().ack();
+    runner.tick(&mut processor).unwrap();
+    assert_eq!(
+        runner.writer().outputs[2..],
+        [checkpoint_at("1"), status("processRecords")]
+    );
+
+    second.ack();
+    processor.handles.pop().unwrap().ack();
+    runner.tick(&mut processor).unwrap();
+    assert_eq!(
+        runner.writer().outputs[4..],
+        [
+            checkpoint_at("4"),
+            "{\"action\":\"status\",\"responseFor\":\"shutdownRequested\"}\n".to_string()
+        ]
+    );
+    assert_eq!(
+        runner.context().last_checkpointed,
+        Some("4".parse().unwrap())
+    );
+    assert_eq!(
+        runner.tick(&mut processor).unwrap(),
+        TickOutcome::InputClosed
+    );
+}
+
+#[test]
+fn test_shard_end_waits_for_outstanding_records() {
+    let mut reader = MockReader::with_input(INITIALIZE.to_string());
+    reader.add_input(records(&["1", "2"]));
+    reader.add_input("{\"action\": \"shardEnded\", \"checkpoint\": \"SHARD_END\"}".to_string());
+    reader.add_input(CHECKPOINTED.to_string());
+    let mut processor = MockAckingProcessor::default();
+    let mut runner = Runner::builder()
+        .reader(reader)
+        .writer(MockWriter::default())
+        .ack_tracker(processor.tracker.clone())
+        .failure_policy(FailurePolicy::Skip)
+        .build();
+
+    runner.tick(&mut processor).unwrap();
+    runner.tick(&mut processor).unwrap();
+    processor.handles.remove(0).ack();
+    runner.tick(&mut processor).unwrap();
+
+    // The processor refuses to end the shard while the second record is still outstanding, so the
+    // runner only checkpoints the first
+    assert_eq!(runner.writer().outputs[2], checkpoint_at("1"));
+}
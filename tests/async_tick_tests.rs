@@ -0,0 +1,90 @@
+#![cfg(feature = "async")]
+mod mocks;
+
+use kcl::asynchronous::tick;
+
+use crate::mocks::mock_processor::MockAsyncProcessor;
+use crate::mocks::mock_reader::MockReader;
+use crate::mocks::mock_writer::MockWriter;
+
+async fn tick_into_processor(messages: &[&str]) -> (MockAsyncProcessor, MockWriter) {
+    let mut processor = MockAsyncProcessor::default();
+    let mut reader = MockReader::default();
+    for message in messages {
+        reader.add_input(message.to_string());
+    }
+    let mut writer = MockWriter::default();
+
+    tick(&mut processor, &mut reader, &mut writer)
+        .await
+        .unwrap();
+
+    (processor, writer)
+}
+
+fn assert_status_response(writer: &MockWriter, status: &str) {
+    let expected_out = format!("{{\"action\":\"status\",\"responseFor\":\"{status}\"}}\n");
+    assert_eq!(writer.outputs.last(), Some(&expected_out))
+}
+
+#[tokio::test]
+async fn test_async_tick_initialize() {
+    let message = "{\"action\" :\"initialize\", \"shardId\": \"shard1\"}";
+    let (processor, writer) = tick_into_processor(&[message]).await;
+
+    assert_eq!(processor.shard, Some("shard1".to_owned()));
+    assert_eq!(writer.outputs.len(), 1);
+    assert_status_response(&writer, "initialize");
+}
+
+#[tokio::test]
+async fn test_async_tick_new_record_with_checkpoint() {
+    let message = "{\"action\": \"processRecords\", \
+        \"records\": [{\
+            \"data\": \"SGVsbG8sIHRoaXMgaXMgYSB0ZXN0Lg==\",\
+            \"partitionKey\": \"1\",\
+            \"sequenceNumber\": \"49590338271490256608559692538361571095921575989136588898\",\
+            \"approximateArrivalTimestamp\": 1570887011763.01}]}";
+    let checkpoint_response = "{\"action\":\"checkpoint\",\"checkpoint\":null,\"error\":null}";
+    let (processor, writer) = tick_into_processor(&[message, checkpoint_response]).await;
+
+    let record = processor.records.last().unwrap();
+    assert_eq!(
+        std::str::from_utf8(record.raw_data.as_slice()).unwrap(),
+        "Hello, this is a test."
+    );
+    assert_eq!(writer.outputs.len(), 2);
+    assert_eq!(
+        writer.outputs[0],
+        "{\"action\":\"checkpoint\",\"sequenceNumber\":null,\"subSequenceNumber\":null}\n"
+    );
+    assert_status_response(&writer, "processRecords");
+}
+
+#[tokio::test]
+#[should_panic(expected = "ThrottlingException, retryable: true")]
+async fn test_async_checkpoint_throttle_exception() {
+    let message = "{\"action\" :\"processRecords\", \"records\": []}";
+    let checkpoint_response =
+        "{\"action\":\"checkpoint\",\"checkpoint\":null,\"error\":\"ThrottlingException\"}";
+    tick_into_processor(&[message, checkpoint_response]).await;
+}
+
+#[tokio::test]
+async fn test_async_tick_lease_lost() {
+    let message = "{\"action\": \"leaseLost\"}";
+    let (processor, writer) = tick_into_processor(&[message]).await;
+
+    assert!(processor.lease_lost);
+    assert_status_response(&writer, "leaseLost");
+}
+
+#[tokio::test]
+async fn test_async_tick_shard_ended() {
+    let message = "{\"action\": \"shardEnded\", \"checkpoint\": \"1234\"}";
+    let checkpointed = "{\"action\":\"checkpoint\",\"checkpoint\":null,\"error\":null}";
+    let (processor, writer) = tick_into_processor(&[message, checkpointed]).await;
+
+    assert!(processor.shard_ended);
+    assert_status_response(&writer, "shardEnded");
+}
@@ -1,9 +1,14 @@
 mod mocks;
 
-use crate::mocks::mock_processor::MockCheckpointingProcessor;
+use crate::mocks::mock_processor::{MockCheckpointAtProcessor, MockCheckpointingProcessor};
 use crate::mocks::mock_reader::MockReader;
 use crate::mocks::mock_writer::MockWriter;
-use kcl::tick;
+use kcl::checkpointer::CheckpointError;
+use kcl::{
+    tick, Backoff, CheckpointAttempt, CheckpointRetryPolicy, ExtendedSequenceNumber, Runner,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 fn tick_into_processor(
     message: &str,
@@ -144,3 +149,168 @@ fn test_checkpoint_unexpected_response_exception() {
         Some(expected_checkpoint_status.to_string())
     );
 }
+
+fn run_checkpointing_at(
+    checkpoint_at: &str,
+    messages: &[&str],
+) -> (MockCheckpointAtProcessor, MockWriter) {
+    let mut reader = MockReader::with_input(
+        "{\"action\": \"initialize\", \"shardId\": \"shard1\", \"sequenceNumber\": \"100\"}"
+            .to_string(),
+    );
+    for message in messages {
+        reader.add_input(message.to_string());
+    }
+    let mut runner = Runner::builder()
+        .reader(reader)
+        .writer(MockWriter::default())
+        .build();
+    let mut processor = MockCheckpointAtProcessor {
+        checkpoint_at: Some(checkpoint_at.parse().unwrap()),
+        ..Default::default()
+    };
+
+    runner.run(&mut processor).unwrap();
+
+    (processor, runner.into_parts().1)
+}
+
+const RECORDS_UP_TO_200: &str = "{\"action\": \"processRecords\", \"records\": [{\
+    \"data\": \"SGVsbG8sIHRoaXMgaXMgYSB0ZXN0Lg==\",\
+    \"partitionKey\": \"1\",\
+    \"sequenceNumber\": \"200\",\
+    \"approximateArrivalTimestamp\": 1570887011763.01}]}";
+const CHECKPOINT_SUCCESS: &str =
+    "{\"action\":\"checkpoint\",\"checkpoint\":\"150\",\"subSequenceNumber\":2,\"error\":null}";
+
+#[test]
+fn test_checkpoint_at_delivered_position() {
+    let (processor, writer) =
+        run_checkpointing_at("150:2", &[RECORDS_UP_TO_200, CHECKPOINT_SUCCESS]);
+
+    assert_eq!(processor.results, vec![Ok(Some("150:2".parse().unwrap()))]);
+    assert_eq!(
+        writer.outputs[1],
+        "{\"action\":\"checkpoint\",\"sequenceNumber\":\"150\",\"subSequenceNumber\":2}\n"
+    );
+}
+
+#[test]
+fn test_checkpoint_after_delivered_records_is_rejected() {
+    let (processor, writer) = run_checkpointing_at("201", &[RECORDS_UP_TO_200]);
+
+    assert_eq!(
+        processor.results,
+        vec![Err(CheckpointError::OutOfRange("201".parse().unwrap()))]
+    );
+    // Only the status responses, the checkpoint was never sent
+    assert_eq!(writer.outputs.len(), 2);
+}
+
+#[test]
+fn test_checkpoint_before_starting_position_is_rejected() {
+    let (processor, writer) = run_checkpointing_at("99", &[RECORDS_UP_TO_200]);
+
+    assert_eq!(
+        processor.results,
+        vec![Err(CheckpointError::OutOfRange("99".parse().unwrap()))]
+    );
+    assert_eq!(writer.outputs.len(), 2);
+}
+
+#[test]
+fn test_checkpoint_at_shard_end() {
+    let (processor, writer) = run_checkpointing_at(
+        "SHARD_END",
+        &[
+            "{\"action\": \"shardEnded\", \"checkpoint\": \"SHARD_END\"}",
+            "{\"action\":\"checkpoint\",\"checkpoint\":\"SHARD_END\",\"error\":null}",
+        ],
+    );
+
+    assert_eq!(
+        processor.results,
+        vec![Ok(Some(ExtendedSequenceNumber::SHARD_END))]
+    );
+    assert_eq!(
+        writer.outputs[1],
+        "{\"action\":\"checkpoint\",\"sequenceNumber\":null,\"subSequenceNumber\":null}\n"
+    );
+}
+
+#[test]
+fn test_shard_end_checkpoint_while_processing_records_is_rejected() {
+    let (processor, _writer) = run_checkpointing_at("SHARD_END", &[RECORDS_UP_TO_200]);
+
+    assert_eq!(
+        processor.results,
+        vec![Err(CheckpointError::OutOfRange(
+            ExtendedSequenceNumber::SHARD_END
+        ))]
+    );
+}
+
+fn run_with_checkpoint_retry(
+    checkpoint_responses: &[&str],
+) -> (MockCheckpointAtProcessor, MockWriter, Vec<u32>) {
+    let mut reader =
+        MockReader::with_input("{\"action\": \"initialize\", \"shardId\": \"shard1\"}".to_string());
+    reader.add_input(RECORDS_UP_TO_200.to_string());
+    for response in checkpoint_responses {
+        reader.add_input(response.to_string());
+    }
+    let attempts = Arc::new(Mutex::new(Vec::new()));
+    let reported = attempts.clone();
+    let mut runner = Runner::builder()
+        .reader(reader)
+        .writer(MockWriter::default())
+        .checkpoint_retry(CheckpointRetryPolicy {
+            max_attempts: 3,
+            backoff: Backoff {
+                initial: Duration::from_millis(1),
+                max: Duration::from_millis(1),
+                multiplier: 1,
+            },
+            on_attempt: Some(Arc::new(move |attempt: &CheckpointAttempt| {
+                reported.lock().unwrap().push(attempt.attempt)
+            })),
+            ..Default::default()
+        })
+        .build();
+    let mut processor = MockCheckpointAtProcessor::default();
+
+    runner.run(&mut processor).unwrap();
+
+    let attempts = attempts.lock().unwrap().clone();
+    (processor, runner.into_parts().1, attempts)
+}
+
+const THROTTLED: &str =
+    "{\"action\":\"checkpoint\",\"checkpoint\":null,\"error\":\"ThrottlingException\"}";
+
+#[test]
+fn test_checkpoint_retries_retryable_errors() {
+    let (processor, writer, attempts) = run_with_checkpoint_retry(&[
+        THROTTLED,
+        THROTTLED,
+        "{\"action\":\"checkpoint\",\"checkpoint\":\"200\",\"error\":null}",
+    ]);
+
+    assert_eq!(processor.results, vec![Ok(Some("200".parse().unwrap()))]);
+    assert_eq!(attempts, vec![1, 2]);
+    // The initialize status, three checkpoint attempts, then the records status
+    assert_eq!(writer.outputs.len(), 5);
+}
+
+#[test]
+fn test_checkpoint_retry_gives_up() {
+    let (processor, writer, attempts) =
+        run_with_checkpoint_retry(&[THROTTLED, THROTTLED, THROTTLED]);
+
+    assert_eq!(
+        processor.results,
+        vec![Err(CheckpointError::ThrottlingException)]
+    );
+    assert_eq!(attempts, vec![1, 2, 3]);
+    assert_eq!(writer.outputs.len(), 5);
+}
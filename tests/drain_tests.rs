@@ -0,0 +1,107 @@
+mod mocks;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use kcl::{MissedDrain, Runner, ShutdownDrain};
+
+use crate::mocks::mock_processor::{MockAckingProcessor, MockProcessor};
+use crate::mocks::mock_reader::MockReader;
+use crate::mocks::mock_writer::MockWriter;
+use crate::mocks::{checkpoint_at, CHECKPOINTED, INITIALIZE};
+
+const RECORDS: &str = "{\"action\": \"processRecords\", \"records\": [\
+    {\"data\": \"\", \"partitionKey\": \"1\", \"sequenceNumber\": \"1\", \"approximateArrivalTimestamp\": 0.0},\
+    {\"data\": \"\", \"partitionKey\": \"1\", \"sequenceNumber\": \"2\", \"approximateArrivalTimestamp\": 0.0}]}";
+const SHUTDOWN_REQUESTED: &str = "{\"action\": \"shutdownRequested\", \"checkpoint\": \"2\"}";
+
+fn reader() -> MockReader {
+    let mut reader = MockReader::with_input(INITIALIZE.to_string());
+    for message in [RECORDS, SHUTDOWN_REQUESTED, CHECKPOINTED] {
+        reader.add_input(message.to_string());
+    }
+
+    reader
+}
+
+/// Runs an acking processor through shutdown, acknowledging the first record right away and the
+/// second from another thread after `second_after`.
+fn run_draining_acks(
+    deadline: Duration,
+    second_after: Duration,
+) -> (Runner<MockReader, MockWriter>, Vec<MissedDrain>) {
+    let missed = Arc::new(Mutex::new(Vec::new()));
+    let reported = missed.clone();
+    let mut processor = MockAckingProcessor::default();
+    let mut runner = Runner::builder()
+        .reader(reader())
+        .writer(MockWriter::default())
+        .ack_tracker(processor.tracker.clone())
+        .shutdown_drain(ShutdownDrain {
+            on_missed: Some(Arc::new(move |drain: &MissedDrain| {
+                reported.lock().unwrap().push(drain.clone())
+            })),
+            ..ShutdownDrain::new(deadline)
+        })
+        .build();
+
+    runner.tick(&mut processor).unwrap();
+    runner.tick(&mut processor).unwrap();
+    let second = processor.handles.pop().unwrap();
+    processor.handles.pop().unwrap().ack();
+    let worker = std::thread::spawn(move || {
+        std::thread::sleep(second_after);
+        second.ack();
+    });
+    runner.tick(&mut processor).unwrap();
+    worker.join().unwrap();
+
+    let missed = missed.lock().unwrap().clone();
+    (runner, missed)
+}
+
+#[test]
+fn test_drain_waits_for_outstanding_records() {
+    let (runner, missed) = run_draining_acks(Duration::from_secs(5), Duration::from_millis(20));
+
+    assert!(missed.is_empty());
+    assert_eq!(runner.writer().outputs[2], checkpoint_at("2"));
+    assert_eq!(
+        runner.context().last_checkpointed,
+        Some("2".parse().unwrap())
+    );
+}
+
+#[test]
+fn test_drain_reports_missed_deadline() {
+    let (runner, missed) = run_draining_acks(Duration::from_millis(20), Duration::from_millis(200));
+
+    assert_eq!(missed.len(), 1);
+    assert_eq!(missed[0].outstanding, 1);
+    // Only the completed record is checkpointed
+    assert_eq!(runner.writer().outputs[2], checkpoint_at("1"));
+}
+
+#[test]
+fn test_drain_checkpoints_delivered_records() {
+    let mut runner = Runner::builder()
+        .reader(reader())
+        .writer(MockWriter::default())
+        .shutdown_drain(ShutdownDrain::new(Duration::from_secs(5)))
+        .build();
+    let mut processor = MockProcessor::default();
+
+    runner.run(&mut processor).unwrap();
+
+    assert!(processor.shutdown_requested);
+    assert!(processor.contexts[1].drain_deadline.is_none());
+    assert!(processor.contexts[2].drain_deadline.is_some());
+    assert_eq!(
+        runner.writer().outputs[2],
+        "{\"action\":\"checkpoint\",\"sequenceNumber\":null,\"subSequenceNumber\":null}\n"
+    );
+    assert_eq!(
+        runner.context().last_checkpointed,
+        Some("2".parse().unwrap())
+    );
+}
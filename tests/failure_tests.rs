@@ -0,0 +1,139 @@
+mod mocks;
+
+use std::time::Duration;
+
+use kcl::{Backoff, FailurePolicy, RunError, Runner};
+
+use crate::mocks::mock_processor::MockFailingProcessor;
+use crate::mocks::mock_reader::MockReader;
+use crate::mocks::mock_writer::MockWriter;
+use crate::mocks::{status, INITIALIZE};
+
+const PROCESS_RECORDS: &str = "{\"action\" :\"processRecords\", \"records\": []}";
+
+fn tick_failing_processor(
+    failures: u32,
+    policy: FailurePolicy,
+) -> (MockFailingProcessor, MockWriter, Result<(), RunError>) {
+    let mut processor = MockFailingProcessor {
+        failures,
+        ..Default::default()
+    };
+    let mut reader = MockReader::with_input(INITIALIZE.to_string());
+    reader.add_input(PROCESS_RECORDS.to_string());
+    let mut runner = Runner::builder()
+        .reader(reader)
+        .writer(MockWriter::default())
+        .failure_policy(policy)
+        .build();
+
+    runner.tick(&mut processor).unwrap();
+    let result = runner.tick(&mut processor).map(|_| ());
+    let (_, mut writer) = runner.into_parts();
+    // Only the outputs for the records are of interest
+    writer.outputs.remove(0);
+
+    (processor, writer, result)
+}
+
+#[cfg(feature = "async")]
+async fn tick_failing_processor_async(
+    failures: u32,
+    policy: FailurePolicy,
+) -> (MockFailingProcessor, MockWriter, Result<(), RunError>) {
+    let mut processor = MockFailingProcessor {
+        failures,
+        ..Default::default()
+    };
+    let mut reader = MockReader::with_input(INITIALIZE.to_string());
+    reader.add_input(PROCESS_RECORDS.to_string());
+    let mut runner = Runner::async_builder()
+        .reader(reader)
+        .writer(MockWriter::default())
+        .failure_policy(policy)
+        .build();
+
+    runner.tick_async(&mut processor).await.unwrap();
+    let result = runner.tick_async(&mut processor).await.map(|_| ());
+    let (_, mut writer) = runner.into_parts();
+    writer.outputs.remove(0);
+
+    (processor, writer, result)
+}
+
+fn retry(max_attempts: u32) -> FailurePolicy {
+    FailurePolicy::Retry {
+        max_attempts,
+        backoff: Backoff {
+            initial: Duration::ZERO,
+            max: Duration::ZERO,
+            multiplier: 2,
+        },
+        exit_code: 3,
+    }
+}
+
+#[test]
+fn test_retry_until_success() {
+    let (processor, writer, result) = tick_failing_processor(2, retry(3));
+
+    assert!(result.is_ok());
+    assert_eq!(processor.attempts, 3);
+    assert_eq!(writer.outputs, vec![status("processRecords")]);
+}
+
+#[test]
+fn test_retry_exhausted() {
+    let (processor, writer, result) = tick_failing_processor(3, retry(3));
+
+    let Err(RunError::Processor(failure)) = result else {
+        panic!("expected processor failure, got {result:?}");
+    };
+    assert_eq!(failure.exit_code, 3);
+    assert_eq!(failure.action, "processRecords");
+    assert_eq!(processor.attempts, 3);
+    assert!(writer.outputs.is_empty());
+}
+
+#[test]
+fn test_skip_acknowledges_message() {
+    let (processor, writer, result) = tick_failing_processor(1, FailurePolicy::Skip);
+
+    assert!(result.is_ok());
+    assert_eq!(processor.attempts, 1);
+    assert_eq!(writer.outputs, vec![status("processRecords")]);
+}
+
+#[test]
+fn test_exit_reports_code() {
+    let (processor, writer, result) = tick_failing_processor(1, FailurePolicy::Exit { code: 42 });
+
+    let error = result.unwrap_err();
+    assert_eq!(error.exit_code(), 42);
+    assert_eq!(processor.attempts, 1);
+    assert!(writer.outputs.is_empty());
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_retry_until_success() {
+    let (processor, writer, result) = tick_failing_processor_async(2, retry(3)).await;
+
+    assert!(result.is_ok());
+    assert_eq!(processor.attempts, 3);
+    assert_eq!(writer.outputs, vec![status("processRecords")]);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_retry_exhausted() {
+    let (processor, writer, result) = tick_failing_processor_async(3, retry(3)).await;
+
+    let Err(RunError::Processor(failure)) = result else {
+        panic!("expected processor failure, got {result:?}");
+    };
+    assert_eq!(failure.exit_code, 3);
+    assert_eq!(failure.action, "processRecords");
+    assert_eq!(processor.attempts, 3);
+    assert!(writer.outputs.is_empty());
+}
@@ -0,0 +1,199 @@
+#![cfg(feature = "testing")]
+
+mod mocks;
+
+use std::time::Duration;
+
+use kcl::checkpointer::CheckpointError;
+use kcl::testing::{FakeDaemon, FaultInjector, Faults, InjectedFault};
+use kcl::{EveryBatch, ShardState, ShutdownDrain};
+
+use crate::mocks::collecting_processor::CollectingProcessor;
+use crate::mocks::mock_processor::MockProcessor;
+
+fn daemon() -> FakeDaemon {
+    (0..10)
+        .fold(
+            FakeDaemon::new().initialize("shardId-000000000000"),
+            |daemon, batch| daemon.records([format!("{batch}")]),
+        )
+        .shard_ended()
+}
+
+#[test]
+fn test_same_seed_injects_same_faults() {
+    let faults = Faults {
+        checkpoint_error_rate: 0.3,
+        checkpoint_errors: vec![CheckpointError::ThrottlingException],
+        duplicate_batch_rate: 0.3,
+        replay_rate: 0.3,
+        lease_loss_rate: 0.05,
+        ..Default::default()
+    };
+    let run = |seed| {
+        let daemon = daemon();
+        let mut processor = CollectingProcessor::default();
+        let report = FaultInjector::with_seed(faults.clone(), seed).run(&daemon, &mut processor);
+        (
+            report.map_or_else(|failure| failure.injected, |report| report.injected),
+            processor.data,
+            daemon.sent(),
+        )
+    };
+
+    let first = run(42);
+    let second = run(42);
+
+    assert!(!first.0.is_empty());
+    assert_eq!(first.0, second.0);
+    assert_eq!(first.1, second.1);
+    assert_eq!(first.2, second.2);
+}
+
+#[test]
+fn test_injects_lease_loss() {
+    let daemon = daemon();
+    let faults = Faults {
+        lease_loss_rate: 1.0,
+        ..Default::default()
+    };
+    let mut processor = CollectingProcessor::default();
+
+    let report = FaultInjector::new(faults)
+        .run(&daemon, &mut processor)
+        .unwrap();
+
+    assert_eq!(report.injected, vec![InjectedFault::LeaseLost]);
+    assert!(processor.data.is_empty());
+    assert_eq!(daemon.statuses(), ["initialize", "leaseLost"]);
+    daemon.assert_all_acknowledged();
+}
+
+#[test]
+fn test_injects_shutdown() {
+    let daemon = daemon();
+    let faults = Faults {
+        shutdown_rate: 1.0,
+        ..Default::default()
+    };
+    let mut processor = CollectingProcessor::default();
+
+    let report = FaultInjector::new(faults)
+        .run(&daemon, &mut processor)
+        .unwrap();
+
+    assert_eq!(report.injected, vec![InjectedFault::ShutdownRequested]);
+    // The rest of the script follows the shutdown request as messages already in flight
+    assert_eq!(daemon.statuses()[..2], ["initialize", "shutdownRequested"]);
+    assert_eq!(processor.data.len(), 10);
+    daemon.assert_all_acknowledged();
+    daemon.assert_shard_end_checkpointed();
+}
+
+#[test]
+fn test_injects_faults_once_the_script_is_exhausted() {
+    let daemon = FakeDaemon::new().initialize("shardId-000000000000");
+    let faults = Faults {
+        shutdown_rate: 1.0,
+        ..Default::default()
+    };
+    let mut processor = CollectingProcessor::default();
+
+    let report = FaultInjector::new(faults)
+        .run(&daemon, &mut processor)
+        .unwrap();
+
+    assert_eq!(report.injected, vec![InjectedFault::ShutdownRequested]);
+    assert_eq!(daemon.statuses(), ["initialize", "shutdownRequested"]);
+}
+
+#[test]
+fn test_injects_faults_through_a_configured_runner() {
+    let daemon = daemon();
+    let faults = Faults {
+        shutdown_rate: 1.0,
+        ..Default::default()
+    };
+    let mut runner = daemon
+        .runner()
+        .checkpoint_strategy(EveryBatch)
+        .shutdown_drain(ShutdownDrain::new(Duration::from_millis(10)))
+        .build();
+    let mut processor = MockProcessor::default();
+
+    let report = FaultInjector::new(faults)
+        .run_with(&daemon, &mut runner, &mut processor)
+        .unwrap();
+
+    assert_eq!(report.injected, vec![InjectedFault::ShutdownRequested]);
+    assert_eq!(runner.context().state, ShardState::ShardEnded);
+    // The strategy checkpoints every batch the processor leaves unchecked
+    assert_eq!(daemon.checkpoints().len(), 11);
+    daemon.assert_all_acknowledged();
+    daemon.assert_shard_end_checkpointed();
+}
+
+#[test]
+fn test_injects_duplicate_and_replayed_batches() {
+    let daemon = FakeDaemon::new()
+        .initialize("shardId-000000000000")
+        .records(["a"])
+        .records(["b"]);
+    let faults = Faults {
+        duplicate_batch_rate: 1.0,
+        replay_rate: 1.0,
+        ..Default::default()
+    };
+    let mut processor = CollectingProcessor::default();
+
+    let report = FaultInjector::new(faults)
+        .run(&daemon, &mut processor)
+        .unwrap();
+
+    assert_eq!(
+        report.injected,
+        vec![
+            InjectedFault::DuplicateBatch,
+            InjectedFault::ReplayedBatch,
+            InjectedFault::DuplicateBatch
+        ]
+    );
+    assert_eq!(
+        processor.data,
+        vec![
+            b"a".to_vec(),
+            b"a".to_vec(),
+            b"b".to_vec(),
+            b"b".to_vec(),
+            b"a".to_vec()
+        ]
+    );
+    daemon.assert_all_acknowledged();
+}
+
+#[test]
+fn test_failure_reports_seed() {
+    let daemon = daemon();
+    let faults = Faults {
+        checkpoint_error_rate: 1.0,
+        checkpoint_errors: vec![CheckpointError::InvalidStateException],
+        ..Default::default()
+    };
+    let mut processor = CollectingProcessor::default();
+
+    let failure = FaultInjector::with_seed(faults, 7)
+        .run(&daemon, &mut processor)
+        .unwrap_err();
+
+    assert_eq!(failure.seed, 7);
+    assert!(failure.to_string().contains("KCL_FAULT_SEED=7"));
+    assert_eq!(
+        processor.checkpoint_errors,
+        vec![CheckpointError::InvalidStateException; 10]
+    );
+    assert_eq!(
+        failure.injected,
+        vec![InjectedFault::CheckpointError(CheckpointError::InvalidStateException); 11]
+    );
+    assert!(daemon.checkpoints().is_empty());
+}
@@ -0,0 +1,136 @@
+#![cfg(feature = "macros")]
+mod mocks;
+
+use kcl::checkpointer::{Checkpoint, CheckpointTarget};
+use kcl::{ProcessRecordsInput, Runner, ShardContext};
+
+use crate::mocks::mock_reader::MockReader;
+use crate::mocks::mock_writer::MockWriter;
+
+/// Only handles records, leaving every other callback to the macro.
+#[derive(Default)]
+struct CountingProcessor {
+    records: usize,
+}
+
+#[kcl::processor]
+impl CountingProcessor {
+    pub fn process_records(
+        &mut self,
+        _context: &ShardContext,
+        input: &ProcessRecordsInput,
+        checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        self.records += self.count(input);
+        checkpointer.checkpoint(CheckpointTarget::LatestDelivered)?;
+        Ok(())
+    }
+
+    fn count(&self, input: &ProcessRecordsInput) -> usize {
+        input.records.len()
+    }
+}
+
+#[allow(dead_code)]
+mod generated_main {
+    #[derive(Default)]
+    pub struct EmptyProcessor;
+
+    #[kcl::processor(main)]
+    impl EmptyProcessor {}
+}
+
+// Attributes on the impl block apply to the inherent impl holding its other methods too, so the
+// helper does not warn
+#[allow(dead_code)]
+mod allowed {
+    #[derive(Default)]
+    pub struct AllowingProcessor;
+
+    #[kcl::processor]
+    #[allow(non_snake_case)]
+    impl AllowingProcessor {
+        fn Helper(&self) {}
+    }
+}
+
+const PROCESS_RECORDS: &str = "{\"action\": \"processRecords\", \
+    \"records\": [{\
+        \"data\": \"SGVsbG8sIHRoaXMgaXMgYSB0ZXN0Lg==\",\
+        \"partitionKey\": \"1\",\
+        \"sequenceNumber\": \"1\",\
+        \"approximateArrivalTimestamp\": 1570887011763.01}]}";
+
+fn run(messages: &[&str], processor: &mut impl kcl::Processor) -> Vec<String> {
+    let mut reader = MockReader::default();
+    reader.add_input("{\"action\" :\"initialize\", \"shardId\": \"shard1\"}".to_string());
+    for message in messages {
+        reader.add_input(message.to_string());
+    }
+    let mut runner = Runner::builder()
+        .reader(reader)
+        .writer(MockWriter::default())
+        .build();
+
+    runner.run(processor).unwrap();
+
+    runner.writer().outputs.clone()
+}
+
+#[test]
+fn test_processor_macro_keeps_handlers() {
+    let mut processor = CountingProcessor::default();
+
+    let outputs = run(
+        &[
+            PROCESS_RECORDS,
+            "{\"action\":\"checkpoint\",\"checkpoint\":\"1\",\"error\":null}",
+        ],
+        &mut processor,
+    );
+
+    assert_eq!(processor.records, 1);
+    assert_eq!(outputs.len(), 3);
+}
+
+#[test]
+fn test_processor_macro_checkpoints_at_shard_end() {
+    let outputs = run(
+        &[
+            "{\"action\": \"shardEnded\", \"checkpoint\": \"SHARD_END\"}",
+            "{\"action\":\"checkpoint\",\"checkpoint\":\"SHARD_END\",\"error\":null}",
+        ],
+        &mut CountingProcessor::default(),
+    );
+
+    assert_eq!(
+        outputs[1],
+        "{\"action\":\"checkpoint\",\"sequenceNumber\":null,\"subSequenceNumber\":null}\n"
+    );
+    assert_eq!(
+        outputs[2],
+        "{\"action\":\"status\",\"responseFor\":\"shardEnded\"}\n"
+    );
+}
+
+#[test]
+fn test_processor_macro_checkpoints_on_shutdown() {
+    let outputs = run(
+        &[
+            PROCESS_RECORDS,
+            "{\"action\":\"checkpoint\",\"checkpoint\":\"1\",\"error\":null}",
+            "{\"action\": \"shutdownRequested\", \"checkpoint\": \"1\"}",
+            "{\"action\":\"checkpoint\",\"checkpoint\":\"1\",\"error\":null}",
+        ],
+        &mut CountingProcessor::default(),
+    );
+
+    assert_eq!(
+        outputs[3],
+        "{\"action\":\"checkpoint\",\"sequenceNumber\":null,\"subSequenceNumber\":null}\n"
+    );
+    assert_eq!(
+        outputs[4],
+        "{\"action\":\"status\",\"responseFor\":\"shutdownRequested\"}\n"
+    );
+}
@@ -0,0 +1,53 @@
+use kcl::checkpointer::{
+    Checkpoint, CheckpointError, CheckpointShardEnd, CheckpointTarget, ShardEndCheckpoint,
+};
+use kcl::{InitializationInput, ProcessRecordsInput, Processor, ShardContext};
+
+/// Collects the data of every record, checkpointing after every batch and at the end of the shard.
+#[derive(Default)]
+pub struct CollectingProcessor {
+    pub data: Vec<Vec<u8>>,
+    pub checkpoint_errors: Vec<CheckpointError>,
+}
+
+impl Processor for CollectingProcessor {
+    fn initialize(
+        &mut self,
+        _context: &ShardContext,
+        _input: &InitializationInput,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+
+    fn process_records(
+        &mut self,
+        _context: &ShardContext,
+        input: &ProcessRecordsInput,
+        checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        self.data
+            .extend(input.records.iter().map(|record| record.raw_data.clone()));
+        if let Err(error) = checkpointer.checkpoint(CheckpointTarget::LatestDelivered) {
+            self.checkpoint_errors.push(error);
+        }
+        Ok(())
+    }
+
+    fn lease_lost(&mut self, _context: &ShardContext) -> eyre::Result<()> {
+        Ok(())
+    }
+    fn shard_ended(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn CheckpointShardEnd,
+    ) -> eyre::Result<ShardEndCheckpoint> {
+        Ok(checkpointer.checkpoint()?)
+    }
+    fn shutdown_requested(
+        &mut self,
+        _context: &ShardContext,
+        _checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+}
@@ -1,40 +1,89 @@
-use crate::mocks::mock_reader::MockReader;
-use crate::mocks::mock_writer::MockWriter;
-use kcl::checkpointer::Checkpointer;
-use kcl::{Processor, Record};
+#[cfg(feature = "async")]
+use kcl::asynchronous::checkpointer::{AsyncCheckpoint, AsyncCheckpointShardEnd};
+use kcl::checkpointer::{Checkpoint, CheckpointShardEnd, ShardEndCheckpoint};
+use kcl::checkpointer::{CheckpointError, CheckpointTarget};
+use kcl::{
+    AckHandle, AckTracker, ExtendedSequenceNumber, InitializationInput, ProcessRecordsInput,
+    Processor, Record, ShardContext,
+};
 
 #[derive(Default)]
 pub struct MockProcessor {
     pub shard: Option<String>,
+    pub resume_from: Option<ExtendedSequenceNumber>,
     pub records: Vec<Record>,
+    pub millis_behind_latest: Option<u64>,
     pub lease_lost: bool,
     pub shard_ended: bool,
     pub shutdown_requested: bool,
+    pub daemon_gone: bool,
+    pub unknown_actions: Vec<String>,
+    pub contexts: Vec<ShardContext>,
 }
 
-impl Processor<MockWriter, MockReader> for MockProcessor {
-    fn initialize(&mut self, shard_id: &str) {
-        self.shard = Some(shard_id.to_owned())
+impl Processor for MockProcessor {
+    fn initialize(
+        &mut self,
+        context: &ShardContext,
+        input: &InitializationInput,
+    ) -> eyre::Result<()> {
+        self.contexts.push(context.clone());
+        self.shard = Some(input.shard_id.clone());
+        self.resume_from = input.sequence_number.clone();
+        Ok(())
     }
 
     fn process_records(
         &mut self,
-        data: &[Record],
-        _checkpointer: &mut Checkpointer<MockWriter, MockReader>,
-    ) {
-        for record in data {
+        context: &ShardContext,
+        input: &ProcessRecordsInput,
+        _checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        self.contexts.push(context.clone());
+        for record in &input.records {
             self.records.push((*record).clone())
         }
+        self.millis_behind_latest = input.millis_behind_latest;
+        Ok(())
     }
 
-    fn lease_lost(&mut self) {
+    fn lease_lost(&mut self, context: &ShardContext) -> eyre::Result<()> {
+        self.contexts.push(context.clone());
         self.lease_lost = true;
+        Ok(())
     }
-    fn shard_ended(&mut self, _checkpointer: &mut Checkpointer<MockWriter, MockReader>) {
+    fn shard_ended(
+        &mut self,
+        context: &ShardContext,
+        checkpointer: &mut dyn CheckpointShardEnd,
+    ) -> eyre::Result<ShardEndCheckpoint> {
+        self.contexts.push(context.clone());
         self.shard_ended = true;
+        Ok(checkpointer.checkpoint()?)
     }
-    fn shutdown_requested(&mut self, _checkpointer: &mut Checkpointer<MockWriter, MockReader>) {
+    fn shutdown_requested(
+        &mut self,
+        context: &ShardContext,
+        _checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        self.contexts.push(context.clone());
         self.shutdown_requested = true;
+        Ok(())
+    }
+    fn daemon_gone(&mut self, context: &ShardContext) -> eyre::Result<()> {
+        self.contexts.push(context.clone());
+        self.daemon_gone = true;
+        Ok(())
+    }
+    fn unknown_action(
+        &mut self,
+        context: &ShardContext,
+        action: &str,
+        _payload: &serde_json::Map<String, serde_json::Value>,
+    ) -> eyre::Result<()> {
+        self.contexts.push(context.clone());
+        self.unknown_actions.push(action.to_owned());
+        Ok(())
     }
 }
 
@@ -47,34 +96,332 @@ pub struct MockCheckpointingProcessor {
     pub shutdown_requested: bool,
 }
 
-impl Processor<MockWriter, MockReader> for MockCheckpointingProcessor {
-    fn initialize(&mut self, shard_id: &str) {
-        self.shard = Some(shard_id.to_owned())
+impl Processor for MockCheckpointingProcessor {
+    fn initialize(
+        &mut self,
+        _context: &ShardContext,
+        input: &InitializationInput,
+    ) -> eyre::Result<()> {
+        self.shard = Some(input.shard_id.clone());
+        Ok(())
+    }
+
+    fn process_records(
+        &mut self,
+        _context: &ShardContext,
+        input: &ProcessRecordsInput,
+        checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        for record in &input.records {
+            self.records.push((*record).clone())
+        }
+        match checkpointer.checkpoint(CheckpointTarget::LatestDelivered) {
+            Ok(_) => {}
+            Err(error) => {
+                panic!("{error}, retryable: {}", error.is_retryable())
+            }
+        };
+        Ok(())
+    }
+
+    fn lease_lost(&mut self, _context: &ShardContext) -> eyre::Result<()> {
+        self.lease_lost = true;
+        Ok(())
+    }
+    fn shard_ended(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn CheckpointShardEnd,
+    ) -> eyre::Result<ShardEndCheckpoint> {
+        self.shard_ended = true;
+        Ok(checkpointer.checkpoint()?)
+    }
+    fn shutdown_requested(
+        &mut self,
+        _context: &ShardContext,
+        _checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        self.shutdown_requested = true;
+        Ok(())
+    }
+}
+
+/// Checkpoints at `checkpoint_at`, or the latest delivered record if not set, when handling records
+/// or a shutdown request, and at the end of the shard once it has ended, keeping the results.
+#[derive(Default)]
+pub struct MockCheckpointAtProcessor {
+    pub checkpoint_at: Option<ExtendedSequenceNumber>,
+    pub results: Vec<Result<Option<ExtendedSequenceNumber>, CheckpointError>>,
+}
+
+impl MockCheckpointAtProcessor {
+    fn target(&self) -> CheckpointTarget<'_> {
+        match &self.checkpoint_at {
+            Some(position) => CheckpointTarget::At(position.clone()),
+            None => CheckpointTarget::LatestDelivered,
+        }
+    }
+}
+
+impl Processor for MockCheckpointAtProcessor {
+    fn initialize(
+        &mut self,
+        _context: &ShardContext,
+        _input: &InitializationInput,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+
+    fn process_records(
+        &mut self,
+        _context: &ShardContext,
+        _input: &ProcessRecordsInput,
+        checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        self.results.push(checkpointer.checkpoint(self.target()));
+        Ok(())
+    }
+
+    fn lease_lost(&mut self, _context: &ShardContext) -> eyre::Result<()> {
+        Ok(())
+    }
+    fn shard_ended(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn CheckpointShardEnd,
+    ) -> eyre::Result<ShardEndCheckpoint> {
+        match checkpointer.checkpoint() {
+            Ok(checkpoint) => {
+                self.results.push(Ok(checkpoint.echoed().cloned()));
+                Ok(checkpoint)
+            }
+            Err(error) => {
+                let report = eyre::eyre!("shard end checkpoint failed: {error}");
+                self.results.push(Err(error));
+                Err(report)
+            }
+        }
+    }
+    fn shutdown_requested(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        self.results.push(checkpointer.checkpoint(self.target()));
+        Ok(())
+    }
+}
+
+/// Tracks every delivered record, leaving acknowledging them to the test.
+#[derive(Default)]
+pub struct MockAckingProcessor {
+    pub tracker: AckTracker,
+    pub handles: Vec<AckHandle>,
+}
+
+impl Processor for MockAckingProcessor {
+    fn initialize(
+        &mut self,
+        _context: &ShardContext,
+        _input: &InitializationInput,
+    ) -> eyre::Result<()> {
+        Ok(())
     }
 
     fn process_records(
         &mut self,
-        data: &[Record],
-        checkpointer: &mut Checkpointer<MockWriter, MockReader>,
-    ) {
-        for record in data {
+        _context: &ShardContext,
+        input: &ProcessRecordsInput,
+        _checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        self.handles.extend(self.tracker.track_all(&input.records));
+        Ok(())
+    }
+
+    fn lease_lost(&mut self, _context: &ShardContext) -> eyre::Result<()> {
+        Ok(())
+    }
+    fn shard_ended(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn CheckpointShardEnd,
+    ) -> eyre::Result<ShardEndCheckpoint> {
+        // Records still being worked on would be lost once the shard is checkpointed at its end
+        eyre::ensure!(
+            self.tracker.outstanding() == 0,
+            "records are still outstanding"
+        );
+        Ok(checkpointer.checkpoint()?)
+    }
+    fn shutdown_requested(
+        &mut self,
+        _context: &ShardContext,
+        _checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+}
+
+/// Fails the first `failures` calls to `process_records` or `shard_ended`, then succeeds.
+#[derive(Default)]
+pub struct MockFailingProcessor {
+    pub failures: u32,
+    pub attempts: u32,
+}
+
+impl Processor for MockFailingProcessor {
+    fn initialize(
+        &mut self,
+        _context: &ShardContext,
+        _input: &InitializationInput,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+
+    fn process_records(
+        &mut self,
+        _context: &ShardContext,
+        _input: &ProcessRecordsInput,
+        _checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        self.attempts += 1;
+        if self.attempts <= self.failures {
+            eyre::bail!("attempt {} failed", self.attempts);
+        }
+        Ok(())
+    }
+
+    fn lease_lost(&mut self, _context: &ShardContext) -> eyre::Result<()> {
+        Ok(())
+    }
+    fn shard_ended(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn CheckpointShardEnd,
+    ) -> eyre::Result<ShardEndCheckpoint> {
+        self.attempts += 1;
+        if self.attempts <= self.failures {
+            eyre::bail!("attempt {} failed", self.attempts);
+        }
+        Ok(checkpointer.checkpoint()?)
+    }
+    fn shutdown_requested(
+        &mut self,
+        _context: &ShardContext,
+        _checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl kcl::asynchronous::AsyncProcessor for MockFailingProcessor {
+    async fn initialize(
+        &mut self,
+        context: &ShardContext,
+        input: &InitializationInput,
+    ) -> eyre::Result<()> {
+        Processor::initialize(self, context, input)
+    }
+
+    async fn process_records(
+        &mut self,
+        _context: &ShardContext,
+        _input: &ProcessRecordsInput,
+        _checkpointer: &mut dyn AsyncCheckpoint,
+    ) -> eyre::Result<()> {
+        self.attempts += 1;
+        if self.attempts <= self.failures {
+            eyre::bail!("attempt {} failed", self.attempts);
+        }
+        Ok(())
+    }
+
+    async fn lease_lost(&mut self, context: &ShardContext) -> eyre::Result<()> {
+        Processor::lease_lost(self, context)
+    }
+    async fn shard_ended(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn AsyncCheckpointShardEnd,
+    ) -> eyre::Result<ShardEndCheckpoint> {
+        self.attempts += 1;
+        if self.attempts <= self.failures {
+            eyre::bail!("attempt {} failed", self.attempts);
+        }
+        Ok(checkpointer.checkpoint().await?)
+    }
+    async fn shutdown_requested(
+        &mut self,
+        _context: &ShardContext,
+        _checkpointer: &mut dyn AsyncCheckpoint,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+#[derive(Default)]
+pub struct MockAsyncProcessor {
+    pub shard: Option<String>,
+    pub records: Vec<Record>,
+    pub lease_lost: bool,
+    pub shard_ended: bool,
+    pub shutdown_requested: bool,
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl kcl::asynchronous::AsyncProcessor for MockAsyncProcessor {
+    async fn initialize(
+        &mut self,
+        _context: &ShardContext,
+        input: &InitializationInput,
+    ) -> eyre::Result<()> {
+        self.shard = Some(input.shard_id.clone());
+        Ok(())
+    }
+
+    async fn process_records(
+        &mut self,
+        _context: &ShardContext,
+        input: &ProcessRecordsInput,
+        checkpointer: &mut dyn AsyncCheckpoint,
+    ) -> eyre::Result<()> {
+        for record in &input.records {
             self.records.push((*record).clone())
         }
-        match checkpointer.checkpoint(None, None) {
+        match checkpointer
+            .checkpoint(CheckpointTarget::LatestDelivered)
+            .await
+        {
             Ok(_) => {}
             Err(error) => {
                 panic!("{error}, retryable: {}", error.is_retryable())
             }
         };
+        Ok(())
     }
 
-    fn lease_lost(&mut self) {
+    async fn lease_lost(&mut self, _context: &ShardContext) -> eyre::Result<()> {
         self.lease_lost = true;
+        Ok(())
     }
-    fn shard_ended(&mut self, _checkpointer: &mut Checkpointer<MockWriter, MockReader>) {
+    async fn shard_ended(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn AsyncCheckpointShardEnd,
+    ) -> eyre::Result<ShardEndCheckpoint> {
         self.shard_ended = true;
+        Ok(checkpointer.checkpoint().await?)
     }
-    fn shutdown_requested(&mut self, _checkpointer: &mut Checkpointer<MockWriter, MockReader>) {
+    async fn shutdown_requested(
+        &mut self,
+        _context: &ShardContext,
+        _checkpointer: &mut dyn AsyncCheckpoint,
+    ) -> eyre::Result<()> {
         self.shutdown_requested = true;
+        Ok(())
     }
 }
@@ -20,9 +20,16 @@ impl MockReader {
 }
 
 impl InputReader for MockReader {
+    // Like stdin, reads an empty line once all input is consumed
     fn next(&mut self) -> eyre::Result<String> {
-        self.lines
-            .pop_front()
-            .ok_or(eyre::eyre!("no input to read"))
+        Ok(self.lines.pop_front().unwrap_or_default())
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl kcl::asynchronous::reader::AsyncInputReader for MockReader {
+    async fn next(&mut self) -> eyre::Result<String> {
+        InputReader::next(self)
     }
 }
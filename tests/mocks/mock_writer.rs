@@ -12,3 +12,11 @@ impl OutputWriter for MockWriter {
         Ok(())
     }
 }
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl kcl::asynchronous::writer::AsyncOutputWriter for MockWriter {
+    async fn write(&mut self, payload: &[u8]) -> eyre::Result<()> {
+        OutputWriter::write(self, payload)
+    }
+}
@@ -1,3 +1,21 @@
+#![allow(dead_code)]
+pub mod collecting_processor;
 pub mod mock_processor;
 pub mod mock_reader;
 pub mod mock_writer;
+
+pub const INITIALIZE: &str = "{\"action\": \"initialize\", \"shardId\": \"shard1\"}";
+/// A successful answer to a checkpoint.
+pub const CHECKPOINTED: &str = "{\"action\":\"checkpoint\",\"checkpoint\":null,\"error\":null}";
+
+/// The checkpoint a processor sends for `sequence_number`.
+pub fn checkpoint_at(sequence_number: &str) -> String {
+    format!(
+        "{{\"action\":\"checkpoint\",\"sequenceNumber\":\"{sequence_number}\",\"subSequenceNumber\":0}}\n"
+    )
+}
+
+/// The status response a processor sends once it handled `action`.
+pub fn status(action: &str) -> String {
+    format!("{{\"action\":\"status\",\"responseFor\":\"{action}\"}}\n")
+}
@@ -0,0 +1,249 @@
+mod mocks;
+
+use kcl::{
+    ExtendedSequenceNumber, LifecycleViolation, ParseMode, Processor, RunError, Runner, ShardState,
+    TickOutcome,
+};
+
+use crate::mocks::mock_processor::{MockCheckpointingProcessor, MockProcessor};
+use crate::mocks::mock_reader::MockReader;
+use crate::mocks::mock_writer::MockWriter;
+
+fn runner_with_input(messages: &[&str]) -> Runner<MockReader, MockWriter> {
+    let mut reader = MockReader::default();
+    for message in messages {
+        reader.add_input(message.to_string());
+    }
+
+    Runner::builder()
+        .reader(reader)
+        .writer(MockWriter::default())
+        .build()
+}
+
+#[test]
+fn test_run_until_input_closed() {
+    let mut runner = runner_with_input(&[
+        "{\"action\" :\"initialize\", \"shardId\": \"shard1\"}",
+        "{\"action\": \"leaseLost\"}",
+    ]);
+    let mut processor = MockProcessor::default();
+
+    runner.run(&mut processor).unwrap();
+
+    assert_eq!(processor.shard, Some("shard1".to_owned()));
+    assert!(processor.lease_lost);
+    assert!(processor.daemon_gone);
+    assert_eq!(runner.writer().outputs.len(), 2);
+}
+
+#[test]
+fn test_run_boxed_processors() {
+    let mut processors: Vec<Box<dyn Processor>> = vec![
+        Box::new(MockProcessor::default()),
+        Box::new(MockCheckpointingProcessor::default()),
+    ];
+
+    for processor in &mut processors {
+        let mut runner = runner_with_input(&[
+            "{\"action\" :\"initialize\", \"shardId\": \"shard1\"}",
+            "{\"action\": \"leaseLost\"}",
+        ]);
+
+        runner.run(processor.as_mut()).unwrap();
+
+        assert_eq!(runner.writer().outputs.len(), 2);
+    }
+}
+
+const PROCESS_RECORDS: &str = "{\"action\": \"processRecords\", \
+    \"records\": [{\
+        \"data\": \"SGVsbG8sIHRoaXMgaXMgYSB0ZXN0Lg==\",\
+        \"partitionKey\": \"1\",\
+        \"sequenceNumber\": \"49590338271490256608559692538361571095921575989136588898\",\
+        \"approximateArrivalTimestamp\": 1570887011763.01}]}";
+
+#[test]
+fn test_run_passes_context_to_callbacks() {
+    let mut runner = runner_with_input(&[
+        "{\"action\" :\"initialize\", \"shardId\": \"shard1\", \"sequenceNumber\": \"TRIM_HORIZON\"}",
+        PROCESS_RECORDS,
+        PROCESS_RECORDS,
+        "{\"action\": \"leaseLost\"}",
+    ]);
+    let mut processor = MockProcessor::default();
+
+    runner.run(&mut processor).unwrap();
+
+    let contexts = &processor.contexts;
+    assert_eq!(contexts.len(), 5);
+    assert!(contexts
+        .iter()
+        .all(|context| context.shard_id == Some("shard1".to_owned())));
+    assert!(contexts
+        .iter()
+        .all(|context| context.starting_position == Some(ExtendedSequenceNumber::TRIM_HORIZON)));
+    assert_eq!(contexts[0].batch_count, 0);
+    assert_eq!(contexts[0].last_batch_at, None);
+    assert_eq!(contexts[1].batch_count, 1);
+    assert_eq!(contexts[2].batch_count, 2);
+    assert_eq!(
+        contexts[2].last_delivered,
+        Some(
+            "49590338271490256608559692538361571095921575989136588898"
+                .parse()
+                .unwrap()
+        )
+    );
+    assert!(contexts[2].last_batch_at.is_some());
+    assert_eq!(contexts[2].last_checkpointed, None);
+    assert_eq!(contexts[4], *runner.context());
+}
+
+#[test]
+fn test_run_records_checkpoint_in_context() {
+    let mut runner = runner_with_input(&[
+        "{\"action\" :\"initialize\", \"shardId\": \"shard1\"}",
+        PROCESS_RECORDS,
+        "{\"action\":\"checkpoint\",\"checkpoint\":null,\"error\":null}",
+    ]);
+    let mut processor = MockCheckpointingProcessor::default();
+
+    runner.run(&mut processor).unwrap();
+
+    let context = runner.context();
+    assert_eq!(context.batch_count, 1);
+    assert_eq!(context.last_checkpointed, context.last_delivered);
+    assert!(context.last_checkpointed.is_some());
+}
+
+#[test]
+fn test_tick_input_closed() {
+    let mut runner = runner_with_input(&[]);
+    let mut processor = MockProcessor::default();
+
+    let outcome = runner.tick(&mut processor).unwrap();
+
+    assert_eq!(outcome, TickOutcome::InputClosed);
+    assert!(processor.daemon_gone);
+    assert!(runner.writer().outputs.is_empty());
+}
+
+#[test]
+fn test_run_invalid_message() {
+    let mut runner = runner_with_input(&["{\"action\": \"initialize\"}"]);
+    let mut processor = MockProcessor::default();
+
+    let error = runner.run(&mut processor).unwrap_err();
+
+    assert!(matches!(error, RunError::Protocol(_)));
+    assert_eq!(error.exit_code(), 3);
+    assert!(!processor.daemon_gone);
+}
+
+#[test]
+fn test_run_unexpected_checkpoint() {
+    let mut runner =
+        runner_with_input(&["{\"action\":\"checkpoint\",\"checkpoint\":null,\"error\":null}"]);
+    let mut processor = MockProcessor::default();
+
+    let error = runner.run(&mut processor).unwrap_err();
+
+    assert!(matches!(error, RunError::Protocol(_)));
+    assert!(runner.writer().outputs.is_empty());
+}
+
+#[test]
+fn test_run_unknown_action_leniently() {
+    let mut runner =
+        runner_with_input(&["{\"action\": \"leaseTransferred\", \"newOwner\": \"worker2\"}"]);
+    let mut processor = MockProcessor::default();
+
+    runner.run(&mut processor).unwrap();
+
+    assert_eq!(
+        processor.unknown_actions,
+        vec!["leaseTransferred".to_string()]
+    );
+    assert_eq!(
+        runner.writer().outputs,
+        vec!["{\"action\":\"status\",\"responseFor\":\"leaseTransferred\"}\n".to_string()]
+    );
+}
+
+#[test]
+fn test_run_unknown_action_strictly() {
+    let mut runner = Runner::builder()
+        .reader(MockReader::with_input(
+            "{\"action\": \"leaseTransferred\", \"newOwner\": \"worker2\"}".to_string(),
+        ))
+        .writer(MockWriter::default())
+        .parse_mode(ParseMode::Strict)
+        .build();
+    let mut processor = MockProcessor::default();
+
+    let error = runner.run(&mut processor).unwrap_err();
+
+    assert!(matches!(error, RunError::Protocol(_)));
+    assert!(processor.unknown_actions.is_empty());
+    assert!(runner.writer().outputs.is_empty());
+}
+
+#[test]
+fn test_run_records_before_initialize() {
+    let mut runner = runner_with_input(&[PROCESS_RECORDS]);
+    let mut processor = MockProcessor::default();
+
+    let error = runner.run(&mut processor).unwrap_err();
+
+    let RunError::Lifecycle(violation) = &error else {
+        panic!("expected lifecycle violation, got {error:?}");
+    };
+    assert_eq!(violation.state, ShardState::Uninitialized);
+    assert_eq!(violation.action, "processRecords");
+    assert_eq!(error.exit_code(), 3);
+    assert!(processor.contexts.is_empty());
+    assert!(runner.writer().outputs.is_empty());
+}
+
+#[test]
+fn test_run_records_after_lease_lost() {
+    let mut runner = runner_with_input(&[
+        "{\"action\" :\"initialize\", \"shardId\": \"shard1\"}",
+        "{\"action\": \"leaseLost\"}",
+        PROCESS_RECORDS,
+    ]);
+    let mut processor = MockProcessor::default();
+
+    let error = runner.run(&mut processor).unwrap_err();
+
+    assert!(matches!(
+        error,
+        RunError::Lifecycle(LifecycleViolation {
+            state: ShardState::LeaseLost,
+            ..
+        })
+    ));
+    let context = runner.context();
+    assert_eq!(context.state, ShardState::LeaseLost);
+    assert_eq!(context.batch_count, 0);
+    assert_eq!(context.last_delivered, None);
+    assert_eq!(context.last_batch_at, None);
+    assert_eq!(runner.writer().outputs.len(), 2);
+}
+
+#[test]
+fn test_run_without_enforcing_lifecycle() {
+    let mut runner = Runner::builder()
+        .reader(MockReader::with_input(PROCESS_RECORDS.to_string()))
+        .writer(MockWriter::default())
+        .enforce_lifecycle(false)
+        .build();
+    let mut processor = MockProcessor::default();
+
+    runner.run(&mut processor).unwrap();
+
+    assert_eq!(runner.context().state, ShardState::Uninitialized);
+    assert_eq!(runner.context().batch_count, 1);
+    assert_eq!(runner.writer().outputs.len(), 1);
+}
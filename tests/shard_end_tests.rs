@@ -0,0 +1,184 @@
+mod mocks;
+
+#[cfg(feature = "testing")]
+use kcl::checkpointer::{CheckpointShardEnd, ShardEndCheckpoint};
+use kcl::{
+    AckTracker, ExtendedSequenceNumber, FailurePolicy, Record, RunError, Runner, RunnerBuilder,
+    ShardEndPolicy,
+};
+
+use crate::mocks::mock_processor::MockFailingProcessor;
+use crate::mocks::mock_reader::MockReader;
+use crate::mocks::mock_writer::MockWriter;
+use crate::mocks::{checkpoint_at, status, CHECKPOINTED, INITIALIZE};
+
+const SHARD_END_CHECKPOINT: &str =
+    "{\"action\":\"checkpoint\",\"sequenceNumber\":null,\"subSequenceNumber\":null}\n";
+
+/// Ticks through a processor failing `shard_ended` once, skipped by the failure policy.
+fn run_failing_shard_end(
+    policy: ShardEndPolicy,
+) -> (Runner<MockReader, MockWriter>, Result<(), RunError>) {
+    run_failing_shard_end_with(policy, |builder| builder)
+}
+
+fn run_failing_shard_end_with(
+    policy: ShardEndPolicy,
+    configure: impl FnOnce(
+        RunnerBuilder<MockReader, MockWriter>,
+    ) -> RunnerBuilder<MockReader, MockWriter>,
+) -> (Runner<MockReader, MockWriter>, Result<(), RunError>) {
+    let mut reader = MockReader::with_input(INITIALIZE.to_string());
+    reader.add_input("{\"action\": \"shardEnded\", \"checkpoint\": \"SHARD_END\"}".to_string());
+    reader.add_input(CHECKPOINTED.to_string());
+    let mut runner = configure(
+        Runner::builder()
+            .reader(reader)
+            .writer(MockWriter::default())
+            .failure_policy(FailurePolicy::Skip)
+            .shard_end_policy(policy),
+    )
+    .build();
+    let mut processor = MockFailingProcessor {
+        failures: 1,
+        ..Default::default()
+    };
+
+    runner.tick(&mut processor).unwrap();
+    let result = runner.tick(&mut processor).map(|_| ());
+
+    (runner, result)
+}
+
+#[test]
+fn test_checkpoints_missed_shard_end() {
+    let (runner, result) = run_failing_shard_end(ShardEndPolicy::Checkpoint);
+
+    assert!(result.is_ok());
+    assert_eq!(
+        runner.writer().outputs[1..],
+        [SHARD_END_CHECKPOINT.to_string(), status("shardEnded")]
+    );
+    assert_eq!(
+        runner.context().last_checkpointed,
+        Some(ExtendedSequenceNumber::SHARD_END)
+    );
+}
+
+#[test]
+fn test_checkpoints_acknowledged_records_of_missed_shard_end_with_outstanding_records() {
+    let tracker = AckTracker::new();
+    let mut handles = tracker.track_all(&[record("1"), record("2")]);
+    handles.remove(0).ack();
+
+    let (runner, result) = run_failing_shard_end_with(ShardEndPolicy::Checkpoint, |builder| {
+        builder.ack_tracker(tracker.clone())
+    });
+
+    assert!(result.is_ok());
+    assert_eq!(
+        runner.writer().outputs[1..],
+        [checkpoint_at("1"), status("shardEnded")]
+    );
+    assert_eq!(
+        runner.context().last_checkpointed,
+        Some("1".parse().unwrap())
+    );
+}
+
+#[test]
+fn test_warns_about_missed_shard_end() {
+    let (runner, result) = run_failing_shard_end(ShardEndPolicy::Warn);
+
+    assert!(result.is_ok());
+    assert_eq!(runner.writer().outputs[1..], [status("shardEnded")]);
+    assert_eq!(runner.context().last_checkpointed, None);
+}
+
+#[test]
+fn test_exits_on_missed_shard_end() {
+    let (runner, result) = run_failing_shard_end(ShardEndPolicy::Exit { code: 4 });
+
+    let Err(RunError::Processor(failure)) = result else {
+        panic!("expected processor failure, got {result:?}");
+    };
+    assert_eq!(failure.action, "shardEnded");
+    assert_eq!(failure.exit_code, 4);
+    // Only the initialize status, the shard end is not acknowledged
+    assert_eq!(runner.writer().outputs.len(), 1);
+}
+
+/// Returns a shard end checkpoint from a checkpointer of its own instead of the one it is handed.
+#[cfg(feature = "testing")]
+struct ForgingProcessor;
+
+#[cfg(feature = "testing")]
+impl kcl::Processor for ForgingProcessor {
+    fn initialize(
+        &mut self,
+        _context: &kcl::ShardContext,
+        _input: &kcl::InitializationInput,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+
+    fn process_records(
+        &mut self,
+        _context: &kcl::ShardContext,
+        _input: &kcl::ProcessRecordsInput,
+        _checkpointer: &mut dyn kcl::checkpointer::Checkpoint,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+
+    fn lease_lost(&mut self, _context: &kcl::ShardContext) -> eyre::Result<()> {
+        Ok(())
+    }
+    fn shard_ended(
+        &mut self,
+        _context: &kcl::ShardContext,
+        _checkpointer: &mut dyn CheckpointShardEnd,
+    ) -> eyre::Result<ShardEndCheckpoint> {
+        let mut own = kcl::testing::RecordingCheckpointer::new();
+        Ok(CheckpointShardEnd::checkpoint(&mut own)?)
+    }
+    fn shutdown_requested(
+        &mut self,
+        _context: &kcl::ShardContext,
+        _checkpointer: &mut dyn kcl::checkpointer::Checkpoint,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_exits_on_shard_end_checkpoint_from_another_checkpointer() {
+    let mut reader = MockReader::with_input(INITIALIZE.to_string());
+    reader.add_input("{\"action\": \"shardEnded\", \"checkpoint\": \"SHARD_END\"}".to_string());
+    let mut runner = Runner::builder()
+        .reader(reader)
+        .writer(MockWriter::default())
+        .shard_end_policy(ShardEndPolicy::Exit { code: 4 })
+        .build();
+
+    runner.tick(&mut ForgingProcessor).unwrap();
+    let result = runner.tick(&mut ForgingProcessor);
+
+    let Err(RunError::Processor(failure)) = result else {
+        panic!("expected processor failure, got {result:?}");
+    };
+    assert_eq!(failure.exit_code, 4);
+    assert_eq!(runner.writer().outputs.len(), 1);
+}
+
+fn record(sequence_number: &str) -> Record {
+    Record {
+        raw_data: vec![],
+        partition_key: "1".to_string(),
+        explicit_hash_key: None,
+        sequence_number: sequence_number.parse().unwrap(),
+        approximate_arrival_timestamp: 0.0,
+        extra: Default::default(),
+    }
+}
@@ -0,0 +1,80 @@
+#![cfg(unix)]
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use kcl::checkpointer::{Checkpoint, CheckpointShardEnd, ShardEndCheckpoint};
+use kcl::{run, InitializationInput, ProcessRecordsInput, Processor, ShardContext};
+
+const CHILD_ENV: &str = "KCL_STDOUT_TEST_CHILD";
+
+struct PrintingProcessor;
+
+impl Processor for PrintingProcessor {
+    fn initialize(
+        &mut self,
+        _context: &ShardContext,
+        input: &InitializationInput,
+    ) -> eyre::Result<()> {
+        println!("stray output while initializing {}", input.shard_id);
+        Ok(())
+    }
+    fn process_records(
+        &mut self,
+        _context: &ShardContext,
+        _input: &ProcessRecordsInput,
+        _checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+    fn lease_lost(&mut self, _context: &ShardContext) -> eyre::Result<()> {
+        Ok(())
+    }
+    fn shard_ended(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn CheckpointShardEnd,
+    ) -> eyre::Result<ShardEndCheckpoint> {
+        Ok(checkpointer.checkpoint()?)
+    }
+    fn shutdown_requested(
+        &mut self,
+        _context: &ShardContext,
+        _checkpointer: &mut dyn Checkpoint,
+    ) -> eyre::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_stray_prints_do_not_reach_protocol_channel() {
+    if std::env::var_os(CHILD_ENV).is_some() {
+        run(&mut PrintingProcessor);
+    }
+
+    let mut child = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "test_stray_prints_do_not_reach_protocol_channel",
+            "--nocapture",
+        ])
+        .env(CHILD_ENV, "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"{\"action\" :\"initialize\", \"shardId\": \"shard1\"}\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stdout.contains("{\"action\":\"status\",\"responseFor\":\"initialize\"}\n"));
+    assert!(!stdout.contains("stray output"));
+    assert!(stderr.contains("stray output while initializing shard1"));
+}
@@ -0,0 +1,118 @@
+mod mocks;
+
+use kcl::{CheckpointEvery, EveryBatch, ExtendedSequenceNumber, Runner, RunnerBuilder};
+
+use crate::mocks::mock_processor::{MockCheckpointAtProcessor, MockProcessor};
+use crate::mocks::mock_reader::MockReader;
+use crate::mocks::mock_writer::MockWriter;
+use crate::mocks::{status, CHECKPOINTED, INITIALIZE};
+
+const RECORD: &str = "{\"action\": \"processRecords\", \"records\": [{\
+    \"data\": \"SGVsbG8sIHRoaXMgaXMgYSB0ZXN0Lg==\",\
+    \"partitionKey\": \"1\",\
+    \"sequenceNumber\": \"200\",\
+    \"approximateArrivalTimestamp\": 1570887011763.01}]}";
+const LATEST_CHECKPOINT: &str =
+    "{\"action\":\"checkpoint\",\"sequenceNumber\":null,\"subSequenceNumber\":null}\n";
+
+/// Returns a builder for a runner reading an initialize message followed by `messages`.
+fn builder_with_input(messages: &[&str]) -> RunnerBuilder<MockReader, MockWriter> {
+    let mut reader = MockReader::with_input(INITIALIZE.to_string());
+    for message in messages {
+        reader.add_input(message.to_string());
+    }
+
+    Runner::builder()
+        .reader(reader)
+        .writer(MockWriter::default())
+}
+
+#[test]
+fn test_every_batch() {
+    let mut runner = builder_with_input(&[RECORD, CHECKPOINTED, RECORD, CHECKPOINTED])
+        .checkpoint_strategy(EveryBatch)
+        .build();
+    let mut processor = MockProcessor::default();
+
+    runner.run(&mut processor).unwrap();
+
+    assert_eq!(
+        runner.writer().outputs[1..],
+        [
+            LATEST_CHECKPOINT.to_string(),
+            status("processRecords"),
+            LATEST_CHECKPOINT.to_string(),
+            status("processRecords"),
+        ]
+    );
+    assert_eq!(runner.context().since_checkpoint.batches, 0);
+}
+
+#[test]
+fn test_every_n_records() {
+    let mut runner = builder_with_input(&[RECORD, RECORD, CHECKPOINTED, RECORD])
+        .checkpoint_strategy(CheckpointEvery {
+            records: Some(2),
+            ..Default::default()
+        })
+        .build();
+    let mut processor = MockProcessor::default();
+
+    runner.run(&mut processor).unwrap();
+
+    assert_eq!(
+        runner.writer().outputs[1..],
+        [
+            status("processRecords"),
+            LATEST_CHECKPOINT.to_string(),
+            status("processRecords"),
+            status("processRecords"),
+        ]
+    );
+    assert_eq!(runner.context().since_checkpoint.records, 1);
+}
+
+#[test]
+fn test_flushes_on_shutdown_requested() {
+    let mut runner = builder_with_input(&[
+        RECORD,
+        "{\"action\": \"shutdownRequested\", \"checkpoint\": \"200\"}",
+        CHECKPOINTED,
+    ])
+    .checkpoint_strategy(CheckpointEvery::default())
+    .build();
+    let mut processor = MockProcessor::default();
+
+    runner.run(&mut processor).unwrap();
+
+    assert_eq!(
+        runner.writer().outputs[1..],
+        [
+            status("processRecords"),
+            LATEST_CHECKPOINT.to_string(),
+            status("shutdownRequested"),
+        ]
+    );
+}
+
+#[test]
+fn test_does_not_flush_after_processor_checkpointed() {
+    let mut runner = builder_with_input(&[
+        "{\"action\": \"shardEnded\", \"checkpoint\": \"SHARD_END\"}",
+        CHECKPOINTED,
+    ])
+    .checkpoint_strategy(EveryBatch)
+    .build();
+    let mut processor = MockCheckpointAtProcessor {
+        checkpoint_at: Some(ExtendedSequenceNumber::SHARD_END),
+        ..Default::default()
+    };
+
+    runner.run(&mut processor).unwrap();
+
+    assert_eq!(processor.results.len(), 1);
+    assert_eq!(
+        runner.writer().outputs[1..],
+        [LATEST_CHECKPOINT.to_string(), status("shardEnded")]
+    );
+}
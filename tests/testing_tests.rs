@@ -0,0 +1,148 @@
+#![cfg(feature = "testing")]
+
+mod mocks;
+
+use std::time::Duration;
+
+use kcl::checkpointer::CheckpointError;
+use kcl::testing::{CheckpointCall, FakeDaemon, RecordingCheckpointer};
+use kcl::{
+    Backoff, CheckpointRetryPolicy, ExtendedSequenceNumber, ProcessRecordsInput, Processor,
+    ShardContext,
+};
+
+use crate::mocks::collecting_processor::CollectingProcessor;
+
+fn position(sequence_number: &str) -> ExtendedSequenceNumber {
+    sequence_number.parse().unwrap()
+}
+
+#[test]
+fn test_recording_checkpointer_records_calls() {
+    let mut checkpointer =
+        RecordingCheckpointer::new().fail_checkpoints([CheckpointError::ThrottlingException]);
+    let mut processor = CollectingProcessor::default();
+    let context = ShardContext::default();
+    let input = ProcessRecordsInput {
+        records: vec![kcl::testing::record(position("1"), "a")],
+        millis_behind_latest: None,
+        extra: Default::default(),
+    };
+
+    processor
+        .process_records(&context, &input, &mut checkpointer)
+        .unwrap();
+    processor
+        .process_records(&context, &input, &mut checkpointer)
+        .unwrap();
+    let shard_end = processor.shard_ended(&context, &mut checkpointer).unwrap();
+
+    assert_eq!(processor.data, vec![b"a".to_vec(), b"a".to_vec()]);
+    assert_eq!(
+        processor.checkpoint_errors,
+        vec![CheckpointError::ThrottlingException]
+    );
+    assert_eq!(
+        checkpointer.calls()[0],
+        CheckpointCall {
+            requested: None,
+            error: Some(CheckpointError::ThrottlingException),
+        }
+    );
+    assert_eq!(
+        checkpointer.checkpointed(),
+        vec![None, Some(&ExtendedSequenceNumber::SHARD_END)]
+    );
+    assert_eq!(shard_end.echoed(), Some(&ExtendedSequenceNumber::SHARD_END));
+}
+
+#[test]
+fn test_fake_daemon_drives_shard_to_its_end() {
+    let daemon = FakeDaemon::new()
+        .initialize("shardId-000000000000")
+        .records(["a", "b"])
+        .records(["c"])
+        .shard_ended();
+    let mut processor = CollectingProcessor::default();
+
+    daemon.run(&mut processor).unwrap();
+
+    assert_eq!(
+        processor.data,
+        vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]
+    );
+    assert_eq!(
+        daemon.checkpoints(),
+        vec![
+            position("2"),
+            position("3"),
+            ExtendedSequenceNumber::SHARD_END
+        ]
+    );
+    daemon.assert_shard_end_checkpointed();
+    daemon.assert_all_acknowledged();
+}
+
+#[test]
+fn test_fake_daemon_answers_checkpoints_with_errors() {
+    let daemon = FakeDaemon::new()
+        .initialize("shardId-000000000000")
+        .records(["a"])
+        .records(["b"])
+        .lease_lost()
+        .fail_checkpoints([CheckpointError::InvalidStateException]);
+    let mut processor = CollectingProcessor::default();
+
+    daemon.run(&mut processor).unwrap();
+
+    assert_eq!(
+        processor.checkpoint_errors,
+        vec![CheckpointError::InvalidStateException]
+    );
+    assert_eq!(daemon.failed_checkpoints(), 1);
+    daemon.assert_checkpointed_at(&position("2"));
+    assert_eq!(
+        daemon.statuses(),
+        [
+            "initialize",
+            "processRecords",
+            "processRecords",
+            "leaseLost"
+        ]
+    );
+}
+
+#[test]
+fn test_fake_daemon_with_configured_runner() {
+    let daemon = FakeDaemon::new()
+        .initialize("shardId-000000000000")
+        .records(["a"])
+        .shutdown_requested()
+        .fail_checkpoints([
+            CheckpointError::ThrottlingException,
+            CheckpointError::UnexpectedResponse,
+        ]);
+    let mut processor = CollectingProcessor::default();
+    let mut runner = daemon
+        .runner()
+        .checkpoint_retry(CheckpointRetryPolicy {
+            backoff: Backoff {
+                initial: Duration::ZERO,
+                max: Duration::ZERO,
+                multiplier: 1,
+            },
+            ..Default::default()
+        })
+        .build();
+
+    runner.run(&mut processor).unwrap();
+
+    // The unexpected response is not retried
+    assert_eq!(
+        processor.checkpoint_errors,
+        vec![CheckpointError::UnexpectedResponse]
+    );
+    assert_eq!(daemon.failed_checkpoints(), 2);
+    assert!(daemon.checkpoints().is_empty());
+    daemon.assert_all_acknowledged();
+}
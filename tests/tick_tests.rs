@@ -1,8 +1,8 @@
 mod mocks;
 
-use kcl::tick;
+use kcl::{tick, ExtendedSequenceNumber};
 
-use crate::mocks::mock_processor::MockProcessor;
+use crate::mocks::mock_processor::{MockCheckpointAtProcessor, MockProcessor};
 use crate::mocks::mock_reader::MockReader;
 use crate::mocks::mock_writer::MockWriter;
 
@@ -31,6 +31,19 @@ fn test_tick_initialize() {
     assert_status_response(&writer, "initialize");
 }
 
+#[test]
+fn test_tick_initialize_with_position() {
+    let message = "{\"action\" :\"initialize\", \"shardId\": \"shard1\", \
+        \"sequenceNumber\": \"TRIM_HORIZON\", \"subSequenceNumber\": 0}";
+    let (processor, writer) = tick_into_processor(message);
+
+    assert_eq!(
+        processor.resume_from,
+        Some(ExtendedSequenceNumber::TRIM_HORIZON)
+    );
+    assert_status_response(&writer, "initialize");
+}
+
 #[test]
 fn test_tick_new_record() {
     let message = "{\"action\": \"processRecords\", \
@@ -38,9 +51,11 @@ fn test_tick_new_record() {
             \"data\": \"SGVsbG8sIHRoaXMgaXMgYSB0ZXN0Lg==\",\
             \"partitionKey\": \"1\",\
             \"sequenceNumber\": \"49590338271490256608559692538361571095921575989136588898\",\
-            \"approximateArrivalTimestamp\": 1570887011763.01}]}";
+            \"approximateArrivalTimestamp\": 1570887011763.01}], \
+        \"millisBehindLatest\": 250}";
     let (processor, writer) = tick_into_processor(message);
 
+    assert_eq!(processor.millis_behind_latest, Some(250));
     let record = processor.records.last().unwrap();
     assert_eq!(
         std::str::from_utf8(record.raw_data.as_slice()).unwrap(),
@@ -62,11 +77,18 @@ fn test_tick_lease_lost() {
 
 #[test]
 fn test_tick_shard_ended() {
-    let message = "{\"action\": \"shardEnded\", \"checkpoint\": \"1234\"}";
-    let (processor, writer) = tick_into_processor(message);
+    let mut processor = MockProcessor::default();
+    let mut reader = MockReader::with_input(
+        "{\"action\": \"shardEnded\", \"checkpoint\": \"1234\"}".to_string(),
+    );
+    reader.add_input("{\"action\":\"checkpoint\",\"checkpoint\":null,\"error\":null}".to_string());
+    let mut writer = MockWriter::default();
+
+    tick(&mut processor, &mut reader, &mut writer).unwrap();
 
     assert!(processor.shard_ended);
-    assert_eq!(writer.outputs.len(), 1);
+    // The shard end checkpoint, then the status response
+    assert_eq!(writer.outputs.len(), 2);
     assert_status_response(&writer, "shardEnded");
 }
 
@@ -79,3 +101,27 @@ fn test_tick_shutdown_requested() {
     assert_eq!(writer.outputs.len(), 1);
     assert_status_response(&writer, "shutdownRequested");
 }
+
+#[test]
+fn test_tick_shutdown_requested_checkpoints_at_position() {
+    let mut processor = MockCheckpointAtProcessor {
+        checkpoint_at: Some("1234".parse().unwrap()),
+        ..Default::default()
+    };
+    let mut reader = MockReader::with_input(
+        "{\"action\": \"shutdownRequested\", \"checkpoint\": \"1234\"}".to_string(),
+    );
+    reader.add_input(
+        "{\"action\":\"checkpoint\",\"checkpoint\":\"1234\",\"error\":null}".to_string(),
+    );
+    let mut writer = MockWriter::default();
+
+    tick(&mut processor, &mut reader, &mut writer).unwrap();
+
+    assert_eq!(processor.results, vec![Ok(Some("1234".parse().unwrap()))]);
+    assert_eq!(
+        writer.outputs[0],
+        "{\"action\":\"checkpoint\",\"sequenceNumber\":\"1234\",\"subSequenceNumber\":0}\n"
+    );
+    assert_status_response(&writer, "shutdownRequested");
+}
@@ -0,0 +1,259 @@
+mod mocks;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use eyre::Result;
+use kcl::checkpointer::{Checkpoint, CheckpointShardEnd, CheckpointTarget, ShardEndCheckpoint};
+use kcl::{
+    Backoff, FailurePolicy, InitializationInput, RunError, Runner, RunnerBuilder, ShardContext,
+    Typed, TypedBatch, TypedProcessor, UndecodablePolicy,
+};
+use serde::Deserialize;
+
+use crate::mocks::mock_reader::MockReader;
+use crate::mocks::mock_writer::MockWriter;
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct Event {
+    id: u32,
+}
+
+/// Collects the decoded events and the number of undecodable records, checkpointing at the last
+/// decoded one, after failing the first `failures` batches.
+#[derive(Default)]
+struct EventProcessor {
+    events: Vec<Event>,
+    undecodable: usize,
+    failures: u32,
+}
+
+impl TypedProcessor<Event> for EventProcessor {
+    fn initialize(&mut self, _context: &ShardContext, _input: &InitializationInput) -> Result<()> {
+        Ok(())
+    }
+
+    fn process_records(
+        &mut self,
+        _context: &ShardContext,
+        batch: &TypedBatch<Event>,
+        checkpointer: &mut dyn Checkpoint,
+    ) -> Result<()> {
+        if self.failures > 0 {
+            self.failures -= 1;
+            eyre::bail!("failing batch");
+        }
+        self.undecodable += batch.undecodable.len();
+        for (metadata, event) in &batch.records {
+            checkpointer.checkpoint(CheckpointTarget::At(metadata.sequence_number.clone()))?;
+            self.events.push(event.clone());
+        }
+        Ok(())
+    }
+
+    fn lease_lost(&mut self, _context: &ShardContext) -> Result<()> {
+        Ok(())
+    }
+
+    fn shard_ended(
+        &mut self,
+        _context: &ShardContext,
+        checkpointer: &mut dyn CheckpointShardEnd,
+    ) -> Result<ShardEndCheckpoint> {
+        Ok(checkpointer.checkpoint()?)
+    }
+
+    fn shutdown_requested(
+        &mut self,
+        _context: &ShardContext,
+        _checkpointer: &mut dyn Checkpoint,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Holds `{"id":1}` and `not json`
+const PROCESS_RECORDS: &str = "{\"action\": \"processRecords\", \
+    \"records\": [{\
+        \"data\": \"eyJpZCI6MX0=\",\
+        \"partitionKey\": \"1\",\
+        \"sequenceNumber\": \"1\",\
+        \"approximateArrivalTimestamp\": 1570887011763.01}, {\
+        \"data\": \"bm90IGpzb24=\",\
+        \"partitionKey\": \"1\",\
+        \"sequenceNumber\": \"2\",\
+        \"approximateArrivalTimestamp\": 1570887011763.01}]}";
+
+fn builder_with_input(messages: &[&str]) -> RunnerBuilder<MockReader, MockWriter> {
+    let mut reader = MockReader::default();
+    reader.add_input("{\"action\" :\"initialize\", \"shardId\": \"shard1\"}".to_string());
+    for message in messages {
+        reader.add_input(message.to_string());
+    }
+
+    Runner::builder()
+        .reader(reader)
+        .writer(MockWriter::default())
+}
+
+fn runner_with_input(messages: &[&str]) -> Runner<MockReader, MockWriter> {
+    builder_with_input(messages).build()
+}
+
+#[test]
+fn test_typed_processor_receives_decoded_records() {
+    let mut runner = runner_with_input(&[
+        PROCESS_RECORDS,
+        "{\"action\":\"checkpoint\",\"checkpoint\":\"1\",\"error\":null}",
+    ]);
+    let mut processor =
+        Typed::new(EventProcessor::default()).undecodable(UndecodablePolicy::Collect);
+
+    runner.run(&mut processor).unwrap();
+
+    let processor = processor.into_processor();
+    assert_eq!(processor.events, vec![Event { id: 1 }]);
+    assert_eq!(processor.undecodable, 1);
+    assert_eq!(
+        runner.writer().outputs[1],
+        "{\"action\":\"checkpoint\",\"sequenceNumber\":\"1\",\"subSequenceNumber\":0}\n"
+    );
+}
+
+#[test]
+fn test_typed_processor_fails_batch_with_undecodable_records() {
+    let mut runner = runner_with_input(&[PROCESS_RECORDS]);
+    let mut processor = Typed::new(EventProcessor::default());
+
+    let result = runner.run(&mut processor);
+
+    assert!(matches!(result, Err(RunError::Processor(_))));
+    assert!(processor.processor().events.is_empty());
+}
+
+#[test]
+fn test_typed_processor_retries_without_dead_lettering_again() {
+    let mut runner = builder_with_input(&[
+        PROCESS_RECORDS,
+        "{\"action\":\"checkpoint\",\"checkpoint\":\"1\",\"error\":null}",
+    ])
+    .failure_policy(FailurePolicy::Retry {
+        max_attempts: 3,
+        backoff: Backoff {
+            initial: Duration::ZERO,
+            max: Duration::ZERO,
+            multiplier: 1,
+        },
+        exit_code: 1,
+    })
+    .build();
+    let dead_letters = Arc::new(AtomicUsize::new(0));
+    let sink = dead_letters.clone();
+    let mut processor = Typed::new(EventProcessor {
+        failures: 2,
+        ..Default::default()
+    })
+    .undecodable(UndecodablePolicy::DeadLetter(Arc::new(move |_| {
+        sink.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    })));
+
+    runner.run(&mut processor).unwrap();
+
+    assert_eq!(processor.processor().events, vec![Event { id: 1 }]);
+    assert_eq!(dead_letters.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_typed_processor_with_custom_codec() {
+    let mut runner = runner_with_input(&[
+        PROCESS_RECORDS,
+        "{\"action\":\"checkpoint\",\"checkpoint\":\"1\",\"error\":null}",
+        "{\"action\":\"checkpoint\",\"checkpoint\":\"2\",\"error\":null}",
+    ]);
+    let mut processor = Typed::new(EventProcessor::default()).codec(|data: &[u8]| {
+        Ok(Event {
+            id: data.len() as u32,
+        })
+    });
+
+    runner.run(&mut processor).unwrap();
+
+    assert_eq!(
+        processor.into_processor().events,
+        vec![Event { id: 8 }, Event { id: 8 }]
+    );
+}
+
+#[cfg(feature = "async")]
+mod asynchronous {
+    use eyre::Result;
+    use kcl::asynchronous::checkpointer::{AsyncCheckpoint, AsyncCheckpointShardEnd};
+    use kcl::asynchronous::{tick, AsyncTypedProcessor};
+    use kcl::checkpointer::ShardEndCheckpoint;
+    use kcl::{InitializationInput, ShardContext, Typed, TypedBatch, UndecodablePolicy};
+
+    use super::{Event, EventProcessor, PROCESS_RECORDS};
+    use crate::mocks::mock_reader::MockReader;
+    use crate::mocks::mock_writer::MockWriter;
+
+    #[async_trait::async_trait]
+    impl AsyncTypedProcessor<Event> for EventProcessor {
+        async fn initialize(
+            &mut self,
+            _context: &ShardContext,
+            _input: &InitializationInput,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        async fn process_records(
+            &mut self,
+            _context: &ShardContext,
+            batch: &TypedBatch<Event>,
+            _checkpointer: &mut dyn AsyncCheckpoint,
+        ) -> Result<()> {
+            self.undecodable += batch.undecodable.len();
+            self.events
+                .extend(batch.records.iter().map(|(_, event)| event.clone()));
+            Ok(())
+        }
+
+        async fn lease_lost(&mut self, _context: &ShardContext) -> Result<()> {
+            Ok(())
+        }
+
+        async fn shard_ended(
+            &mut self,
+            _context: &ShardContext,
+            checkpointer: &mut dyn AsyncCheckpointShardEnd,
+        ) -> Result<ShardEndCheckpoint> {
+            Ok(checkpointer.checkpoint().await?)
+        }
+
+        async fn shutdown_requested(
+            &mut self,
+            _context: &ShardContext,
+            _checkpointer: &mut dyn AsyncCheckpoint,
+        ) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_typed_processor_receives_decoded_records() {
+        let mut reader = MockReader::with_input(PROCESS_RECORDS.to_string());
+        let mut writer = MockWriter::default();
+        let mut processor =
+            Typed::new(EventProcessor::default()).undecodable(UndecodablePolicy::Collect);
+
+        tick(&mut processor, &mut reader, &mut writer)
+            .await
+            .unwrap();
+
+        let processor = processor.into_processor();
+        assert_eq!(processor.events, vec![Event { id: 1 }]);
+        assert_eq!(processor.undecodable, 1);
+    }
+}